@@ -30,7 +30,18 @@ async function main() {
   const uploadReceipt = await client.upload(firstAccount.address, wasmCode, "auto");
   console.log("Upload successful, code ID:", uploadReceipt.codeId);
 
-  const initMsg = {};
+  const initMsg = {
+    guardian: null,
+    weight_token: "untrn",
+    protocol_fee: "0.1",
+    max_members_per_cooperative: 20,
+    max_tokens_per_cooperative: 5,
+    governance: {
+      quorum: "1",
+      voting_period: 604800,
+      vote_lock_period: 86400,
+    },
+  };
 
   const instantiateReceipt = await client.instantiate(
     firstAccount.address, 
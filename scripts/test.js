@@ -262,7 +262,8 @@ class CooperativeClient {
     cooperativeName,
     proposalId,
     weight,
-    aye
+    aye,
+    conviction = "None"
   ) {
     if (!this.client || !this.address) throw new Error("Client not initialized");
     
@@ -272,6 +273,7 @@ class CooperativeClient {
         proposal_id: proposalId,
         weight,
         aye,
+        conviction,
       },
     };
 
@@ -345,7 +347,9 @@ class CooperativeClient {
    */
   async withdrawContributionAndReward(
     cooperativeName,
-    token
+    token,
+    amount = null,
+    rewardsOnly = false
   ) {
     if (!this.client || !this.address) throw new Error("Client not initialized");
     
@@ -353,6 +357,8 @@ class CooperativeClient {
       withdraw_contribution_and_reward: {
         cooperative_name: cooperativeName,
         token,
+        amount,
+        rewards_only: rewardsOnly,
       },
     };
 
@@ -613,6 +619,7 @@ const runExample = async () => {
             share: [[0, "0"]], 
             joined_at: Math.floor(Date.now() / 1000), // Current timestamp in seconds
             reputation_score: "1.0",
+            auto_compound: false,
             active_loans: [],
           }
       ];
@@ -622,12 +629,16 @@ const runExample = async () => {
             denom: "untrn",
             is_native: true,
             max_loan_ratio: "0.7", // 70%
+            decimals: 6,
+            symbol: "NTRN",
           },
           {
             denom: "tATOM",
             contract_addr: "neutron1sr60e2velepytzsdyuutcmccl9n2p2lu3pjcggllxyc9rzyu562sqegazj", // tATOM
             is_native: false,
             max_loan_ratio: "0.65", // 65%
+            decimals: 6,
+            symbol: "tATOM",
           }
       ];
       
@@ -670,22 +681,18 @@ const runExample = async () => {
       
       // Example: Create a proposal to whitelist a new token
       const proposal = {
-          id: 1, // This will be assigned by the contract
           description: "Add NGN as a whitelisted token",
           data: {
             denom: "tNGN", 
             is_native: false,
             token_addr: "neutron1he6zd5kk03cs5ywxk5tth9qfewxwnh7k9hjwekr7gs9gl9argadsqdc9rp", // tNGN
             max_loan_ratio: "0.6", // 60%
+            decimals: 6,
+            symbol: "tNGN",
           },
-          votes: [],
-          aye_count: 0,
-          nay_count: 0,
-          aye_weights: 0,
-          nay_weights: 0,
           end_time: Math.floor(Date.now() / 1000) + (60 * 60 * 24 * 7), // 1 week from now
-          proposal_type: "WhitelistToken",
-          executed: false
+          quorum: null, // Falls back to the governance quorum
+          proposal_type: "WhitelistToken"
       };
       
       await client.propose(
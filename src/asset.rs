@@ -0,0 +1,203 @@
+use std::fmt;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Env, MessageInfo, QuerierWrapper, StdResult,
+    Storage, Uint128, WasmMsg,
+};
+use cw20::{AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse};
+
+use crate::state::{
+    ProposalDeposit, RegisteredToken, WhitelistedToken, WhitelistedTokenId, TOKENS,
+};
+use crate::ContractError;
+
+// Native coin or CW20 token
+#[cw_serde]
+pub enum AssetInfo {
+    Native { denom: String },
+    Cw20 { contract_addr: Addr },
+}
+
+// Amount of a native coin or CW20 token
+#[cw_serde]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+impl AssetInfo {
+    pub fn from_whitelisted(token: &WhitelistedToken) -> Result<Self, ContractError> {
+        if token.is_native {
+            Ok(AssetInfo::Native {
+                denom: token.denom.clone(),
+            })
+        } else {
+            let contract_addr = token
+                .contract_addr
+                .clone()
+                .ok_or(ContractError::InvalidToken {})?;
+            Ok(AssetInfo::Cw20 { contract_addr })
+        }
+    }
+
+    pub fn from_registered(token: &RegisteredToken) -> Result<Self, ContractError> {
+        if token.is_native {
+            Ok(AssetInfo::Native {
+                denom: token.denom.clone(),
+            })
+        } else {
+            let contract_addr = token
+                .contract_addr
+                .clone()
+                .ok_or(ContractError::InvalidToken {})?;
+            Ok(AssetInfo::Cw20 { contract_addr })
+        }
+    }
+
+    // Whether a whitelisted token refers to this asset
+    pub fn matches(&self, token: &WhitelistedToken) -> bool {
+        match self {
+            AssetInfo::Native { denom } => token.is_native && token.denom == *denom,
+            AssetInfo::Cw20 { contract_addr } => {
+                !token.is_native && token.contract_addr.as_ref() == Some(contract_addr)
+            }
+        }
+    }
+
+    // Id of the asset in the protocol token registry
+    pub fn token_id(&self, storage: &dyn Storage) -> Result<WhitelistedTokenId, ContractError> {
+        TOKENS
+            .may_load(storage, self.to_string())?
+            .ok_or(ContractError::InvalidToken {})
+    }
+
+    pub fn balance_of(&self, querier: &QuerierWrapper, account: &Addr) -> StdResult<Uint128> {
+        match self {
+            AssetInfo::Native { denom } => Ok(querier.query_balance(account, denom)?.amount),
+            AssetInfo::Cw20 { contract_addr } => {
+                let balance: BalanceResponse = querier.query_wasm_smart(
+                    contract_addr,
+                    &Cw20QueryMsg::Balance {
+                        address: account.to_string(),
+                    },
+                )?;
+                Ok(balance.balance)
+            }
+        }
+    }
+
+    // Decimals reported by the token itself, native coins carry none on chain
+    pub fn decimals(&self, querier: &QuerierWrapper) -> StdResult<Option<u8>> {
+        match self {
+            AssetInfo::Native { .. } => Ok(None),
+            AssetInfo::Cw20 { contract_addr } => {
+                let token_info: TokenInfoResponse =
+                    querier.query_wasm_smart(contract_addr, &Cw20QueryMsg::TokenInfo {})?;
+                Ok(Some(token_info.decimals))
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetInfo::Native { denom } => write!(f, "{}", denom),
+            AssetInfo::Cw20 { contract_addr } => write!(f, "{}", contract_addr),
+        }
+    }
+}
+
+impl Asset {
+    pub fn new(info: AssetInfo, amount: Uint128) -> Self {
+        Asset { info, amount }
+    }
+
+    // Takes the asset from the sender: native coins must be attached to the message,
+    // CW20 tokens are pulled from the sender's allowance with the returned message
+    pub fn transfer_in(
+        &self,
+        querier: &QuerierWrapper,
+        env: &Env,
+        info: &MessageInfo,
+    ) -> Result<Option<CosmosMsg>, ContractError> {
+        match &self.info {
+            AssetInfo::Native { denom } => {
+                let coin = info
+                    .funds
+                    .iter()
+                    .find(|coin| coin.denom == *denom)
+                    .ok_or(ContractError::NoFunds {})?;
+
+                if coin.amount != self.amount {
+                    return Err(ContractError::FundsMustMatchAmount {});
+                }
+
+                Ok(None)
+            }
+            AssetInfo::Cw20 { contract_addr } => {
+                if self.info.balance_of(querier, &info.sender)? < self.amount {
+                    return Err(ContractError::InsufficientFunds {});
+                }
+
+                let allowance: AllowanceResponse = querier.query_wasm_smart(
+                    contract_addr,
+                    &Cw20QueryMsg::Allowance {
+                        owner: info.sender.to_string(),
+                        spender: env.contract.address.to_string(),
+                    },
+                )?;
+                if allowance.allowance < self.amount {
+                    return Err(ContractError::InsufficientAllowance {});
+                }
+
+                Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: contract_addr.to_string(),
+                    msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                        owner: info.sender.to_string(),
+                        recipient: env.contract.address.to_string(),
+                        amount: self.amount,
+                    })?,
+                    funds: vec![],
+                })))
+            }
+        }
+    }
+
+    // Sends the asset from the contract to `recipient`
+    pub fn transfer_out(&self, recipient: &Addr) -> StdResult<CosmosMsg> {
+        match &self.info {
+            AssetInfo::Native { denom } => Ok(CosmosMsg::Bank(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount: self.amount,
+                }],
+            })),
+            AssetInfo::Cw20 { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            })),
+        }
+    }
+}
+
+impl From<&ProposalDeposit> for Asset {
+    fn from(deposit: &ProposalDeposit) -> Self {
+        let info = if deposit.is_native {
+            AssetInfo::Native {
+                denom: deposit.token.clone(),
+            }
+        } else {
+            AssetInfo::Cw20 {
+                contract_addr: Addr::unchecked(&deposit.token),
+            }
+        };
+        Asset::new(info, deposit.amount)
+    }
+}
@@ -1,11 +1,13 @@
 use cosmwasm_schema::write_api;
 
-use ajor::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use ajor::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 
 fn main() {
     write_api! {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        migrate: MigrateMsg,
+        sudo: SudoMsg,
     }
 }
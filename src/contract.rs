@@ -1,24 +1,31 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    entry_point, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut,
-    Env, MessageInfo, Order, Response, StdResult, Uint128, WasmMsg,
+    entry_point, from_json, to_json_binary, Addr, Binary, CosmosMsg, Decimal, Decimal256, Deps,
+    DepsMut, Env, MessageInfo, Order, Response, StdResult, Storage, Uint128, Uint256,
 };
 
 use crate::state::{
-    Cooperative, Loan, LoanStatus, Member, Proposal, ProposalOutcome, ProposalType, RiskProfile,
-    Vote, WhitelistedToken, COOPERATIVES, COOPERATIVES_PROPOSALS, MEMBERS, PRICES, PROPOSALS,
-    TOKENS,
+    Conviction, Cooperative, CooperativeRewardsPool, Loan, LoanStatus, Member, MemberRewards,
+    Proposal, ProposalOutcome, ProposalType, QueuedWithdrawal, RegisteredToken, RiskProfile, Vote,
+    WhitelistedToken, WhitelistedTokenId, COOPERATIVES, COOPERATIVE_MEMBERS, LOANS, MEMBERS,
+    MEMBER_REWARDS, PRICES, PROPOSALS, PROTOCOL_TREASURY, REWARDS_POOLS, TOKENS, TOKEN_REGISTRY,
+    VOTES, WITHDRAWAL_QUEUE,
 };
 
-use cw2::set_contract_version;
+use cw2::{ensure_from_older_version, set_contract_version};
 use execute::{
-    execute_borrow, execute_create_cooperative, execute_fund_cooperative, execute_proposal,
-    execute_propose, execute_repay, execute_update_price, execute_vote, execute_withdraw_weight,
-    withdraw_contribution_and_rewards,
+    execute_accept_ownership, execute_borrow, execute_cancel_ownership_transfer,
+    execute_cancel_proposal, execute_cancel_withdrawal, execute_claim_rewards,
+    execute_create_cooperative, execute_fund_cooperative, execute_pause, execute_proposal,
+    execute_propose, execute_propose_new_owner, execute_receive, execute_repay,
+    execute_set_auto_compound, execute_set_guardian, execute_settle_proposal_deposit,
+    execute_update_config, execute_update_price, execute_vote, execute_withdraw_protocol_fees,
+    execute_withdraw_weight, withdraw_contribution_and_rewards,
 };
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::migrations;
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 use crate::state::{State, STATE};
 
 // version info for migration info
@@ -30,24 +37,349 @@ pub fn instantiate(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender,
+    };
+
+    let guardian = msg
+        .guardian
+        .map(|guardian| deps.api.addr_validate(&guardian))
+        .transpose()?;
+
     let state = State {
-        owner: info.sender.clone(),
-        weight_token: "untrn".to_string(),
+        owner: owner.clone(),
+        guardian,
+        weight_token: msg.weight_token,
         total_corporatives: 0,
         total_pooled_funds: vec![],
         current_proposal_id: 0,
         current_whitelisted_token_id: 0,
         current_loan_id: 0,
+        current_withdrawal_id: 0,
+        protocol_fee: msg.protocol_fee,
+        max_members_per_cooperative: msg.max_members_per_cooperative,
+        max_tokens_per_cooperative: msg.max_tokens_per_cooperative,
+        governance: msg.governance,
     };
+    validate_config(&state)?;
     STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
         .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender))
+        .add_attribute("owner", owner)
+        .add_attribute("weight_token", state.weight_token))
+}
+
+// Checks the configurable protocol parameters
+fn validate_config(state: &State) -> Result<(), ContractError> {
+    let invalid = |msg: &str| ContractError::InvalidConfig {
+        msg: msg.to_string(),
+    };
+
+    // Native denoms are 3 to 128 characters, starting with a letter
+    let denom = &state.weight_token;
+    if denom.len() < 3
+        || denom.len() > 128
+        || !denom.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !denom
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c))
+    {
+        return Err(invalid("weight token must be a valid native denom"));
+    }
+
+    if state.protocol_fee > Decimal::one() {
+        return Err(invalid("protocol fee cannot exceed 100%"));
+    }
+
+    if state.max_members_per_cooperative == 0 {
+        return Err(invalid("max members per cooperative must be positive"));
+    }
+
+    if state.max_tokens_per_cooperative == 0 {
+        return Err(invalid("max tokens per cooperative must be positive"));
+    }
+
+    if state.governance.quorum.is_zero() {
+        return Err(invalid("default quorum must be positive"));
+    }
+
+    if state.governance.voting_period == 0 {
+        return Err(invalid("voting period must be positive"));
+    }
+
+    Ok(())
+}
+
+// Amount recorded for a token in a list of (token id, amount) pairs
+fn amount_of(amounts: &[(WhitelistedTokenId, Uint128)], token_id: WhitelistedTokenId) -> Uint128 {
+    amounts
+        .iter()
+        .find(|(id, _)| *id == token_id)
+        .map(|(_, amount)| *amount)
+        .unwrap_or_default()
+}
+
+fn add_amount(
+    amounts: &mut Vec<(WhitelistedTokenId, Uint128)>,
+    token_id: WhitelistedTokenId,
+    amount: Uint128,
+) {
+    match amounts.iter_mut().find(|(id, _)| *id == token_id) {
+        Some(entry) => entry.1 += amount,
+        None => amounts.push((token_id, amount)),
+    }
+}
+
+fn sub_amount(
+    amounts: &mut [(WhitelistedTokenId, Uint128)],
+    token_id: WhitelistedTokenId,
+    amount: Uint128,
+) -> StdResult<()> {
+    if amount.is_zero() {
+        return Ok(());
+    }
+
+    let entry = amounts
+        .iter_mut()
+        .find(|(id, _)| *id == token_id)
+        .ok_or_else(|| cosmwasm_std::StdError::generic_err("amount not found"))?;
+    entry.1 = entry.1.checked_sub(amount)?;
+    Ok(())
+}
+
+// Pool shares worth `amount` at the cooperative exchange rate, rounded down. The first
+// deposit into a pool mints shares one to one.
+fn shares_for(cooperative: &Cooperative, token_id: WhitelistedTokenId, amount: Uint128) -> Uint128 {
+    let total_funds = amount_of(&cooperative.total_funds, token_id);
+    let total_shares = amount_of(&cooperative.total_shares, token_id);
+    if total_funds.is_zero() || total_shares.is_zero() {
+        return amount;
+    }
+
+    amount.multiply_ratio(total_shares, total_funds)
+}
+
+// Pool shares worth at least `amount`, rounded up
+fn shares_to_cover(
+    cooperative: &Cooperative,
+    token_id: WhitelistedTokenId,
+    amount: Uint128,
+) -> Uint128 {
+    let shares = shares_for(cooperative, token_id, amount);
+    if shares_value(cooperative, token_id, shares) < amount {
+        return shares + Uint128::one();
+    }
+
+    shares
+}
+
+// Funds redeemable for pool shares at the cooperative exchange rate, rounded down
+fn shares_value(
+    cooperative: &Cooperative,
+    token_id: WhitelistedTokenId,
+    shares: Uint128,
+) -> Uint128 {
+    let total_shares = amount_of(&cooperative.total_shares, token_id);
+    if total_shares.is_zero() {
+        return Uint128::zero();
+    }
+
+    shares.multiply_ratio(amount_of(&cooperative.total_funds, token_id), total_shares)
+}
+
+// Spreads the rewards received since the last update over the pool shares of the token.
+// Only the rewards the index can represent are marked indexed, the rounding remainder is
+// carried to the next update.
+fn accrue_rewards(pool: &mut CooperativeRewardsPool, total_shares: Uint128) -> StdResult<()> {
+    let new_rewards = pool.total_rewards.checked_sub(pool.indexed_rewards)?;
+    if new_rewards.is_zero() || total_shares.is_zero() {
+        return Ok(());
+    }
+
+    let increment = Decimal256::checked_from_ratio(new_rewards, total_shares)
+        .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))?;
+    let indexed = Uint128::try_from(Uint256::from(total_shares).mul_floor(increment))?;
+
+    pool.reward_per_share = pool.reward_per_share.checked_add(increment)?;
+    pool.indexed_rewards = pool.indexed_rewards.checked_add(indexed)?;
+    Ok(())
+}
+
+// Rewards of a member's pool shares, including those earned since they were last settled
+fn earned_rewards(
+    pool: &CooperativeRewardsPool,
+    member_rewards: &MemberRewards,
+    shares: Uint128,
+) -> StdResult<Uint128> {
+    let index_diff = pool
+        .reward_per_share
+        .checked_sub(member_rewards.reward_per_share)?;
+    let earned = Uint128::try_from(Uint256::from(shares).mul_floor(index_diff))?;
+    Ok(member_rewards.pending_rewards.checked_add(earned)?)
+}
+
+fn load_member_rewards(
+    storage: &dyn Storage,
+    cooperative_name: &str,
+    member_addr: &Addr,
+    token_id: WhitelistedTokenId,
+) -> StdResult<MemberRewards> {
+    let key = (cooperative_name.to_string(), member_addr.clone(), token_id);
+    Ok(MEMBER_REWARDS
+        .may_load(storage, key)?
+        .unwrap_or(MemberRewards {
+            cooperative_name: cooperative_name.to_string(),
+            member_address: member_addr.clone(),
+            token_id,
+            last_withdrawn_rewards: Uint128::zero(),
+            reward_per_share: Decimal256::zero(),
+            pending_rewards: Uint128::zero(),
+        }))
+}
+
+// Pool funds held by the contract that are neither lent out nor owed to queued withdrawals
+fn available_liquidity(cooperative: &Cooperative, token_id: WhitelistedTokenId) -> Uint128 {
+    amount_of(&cooperative.total_funds, token_id)
+        .saturating_sub(amount_of(&cooperative.total_borrowed, token_id))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Refuses migrating from another contract or a newer version
+    let previous_version =
+        ensure_from_older_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    migrations::migrate_storage(deps.storage, &deps.querier, &env, &previous_version)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("previous_version", previous_version.to_string())
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::Pause {
+            cooperative_name,
+            operations,
+        } => sudo::pause(deps, cooperative_name, operations, true),
+        SudoMsg::Unpause {
+            cooperative_name,
+            operations,
+        } => sudo::pause(deps, cooperative_name, operations, false),
+        SudoMsg::ForceUpdatePrices { prices } => sudo::force_update_prices(deps, env, prices),
+        SudoMsg::SetOwner { owner } => sudo::set_owner(deps, owner),
+        SudoMsg::FreezeCooperative { cooperative_name } => {
+            sudo::freeze_cooperative(deps, cooperative_name, true)
+        }
+        SudoMsg::UnfreezeCooperative { cooperative_name } => {
+            sudo::freeze_cooperative(deps, cooperative_name, false)
+        }
+    }
+}
+
+pub mod sudo {
+    use cosmwasm_std::Timestamp;
+
+    use crate::state::{
+        PausableOperation, Price, PENDING_OWNER, SUDO_COOPERATIVE_PAUSED, SUDO_PAUSED,
+    };
+
+    use super::execute::set_paused;
+    use super::*;
+
+    pub fn pause(
+        deps: DepsMut,
+        cooperative_name: Option<String>,
+        operations: Option<Vec<PausableOperation>>,
+        paused: bool,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.map(|name| name.trim().to_lowercase());
+        if let Some(name) = &cooperative_name {
+            if !COOPERATIVES.has(deps.storage, name.clone()) {
+                return Err(ContractError::CooperativeNotFound {});
+            }
+        }
+
+        let operations = operations.unwrap_or_else(|| PausableOperation::ALL.to_vec());
+        set_paused(
+            deps.storage,
+            &SUDO_PAUSED,
+            &SUDO_COOPERATIVE_PAUSED,
+            cooperative_name.as_deref(),
+            &operations,
+            paused,
+        )?;
+
+        Ok(Response::new()
+            .add_attribute("action", if paused { "sudo_pause" } else { "sudo_unpause" })
+            .add_attribute("cooperative", cooperative_name.unwrap_or_default()))
+    }
+
+    // Sets prices without the owner, e.g. when the price feed is compromised
+    pub fn force_update_prices(
+        deps: DepsMut,
+        env: Env,
+        prices: Vec<(String, Decimal)>,
+    ) -> Result<Response, ContractError> {
+        for (token_addr, usd_price) in &prices {
+            let token_id = TOKENS.load(deps.storage, token_addr.clone())?;
+            let price = Price {
+                latest_price_to_usd: *usd_price,
+                last_updated_at: Timestamp::from_seconds(env.block.time.seconds()),
+            };
+            PRICES.save(deps.storage, token_id, &price)?;
+        }
+
+        Ok(Response::new()
+            .add_attribute("action", "sudo_force_update_prices")
+            .add_attribute("count", prices.len().to_string()))
+    }
+
+    pub fn set_owner(deps: DepsMut, owner: String) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+        state.owner = deps.api.addr_validate(&owner)?;
+        STATE.save(deps.storage, &state)?;
+
+        // Discard any transfer started by the previous owner
+        PENDING_OWNER.remove(deps.storage);
+
+        Ok(Response::new()
+            .add_attribute("action", "sudo_set_owner")
+            .add_attribute("owner", state.owner))
+    }
+
+    pub fn freeze_cooperative(
+        deps: DepsMut,
+        cooperative_name: String,
+        frozen: bool,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let mut cooperative = COOPERATIVES
+            .may_load(deps.storage, cooperative_name.clone())?
+            .ok_or(ContractError::CooperativeNotFound {})?;
+
+        cooperative.frozen = frozen;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+
+        Ok(Response::new()
+            .add_attribute(
+                "action",
+                if frozen {
+                    "sudo_freeze_cooperative"
+                } else {
+                    "sudo_unfreeze_cooperative"
+                },
+            )
+            .add_attribute("cooperative", cooperative_name))
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -67,12 +399,14 @@ pub fn execute(
             risk_profile,
             initial_members,
             initial_whitelisted_tokens,
+            proposal_deposit,
         } => execute_create_cooperative(
             deps,
             name,
             risk_profile,
             initial_members,
             initial_whitelisted_tokens,
+            proposal_deposit,
         ),
         ExecuteMsg::FundCooperative {
             cooperative_name,
@@ -109,7 +443,17 @@ pub fn execute(
             proposal_id,
             weight,
             aye,
-        } => execute_vote(deps, env, info, cooperative_name, proposal_id, weight, aye),
+            conviction,
+        } => execute_vote(
+            deps,
+            env,
+            info,
+            cooperative_name,
+            proposal_id,
+            weight,
+            aye,
+            conviction,
+        ),
         ExecuteMsg::WithdrawWeight {
             cooperative_name,
             proposal_id,
@@ -117,314 +461,694 @@ pub fn execute(
         ExecuteMsg::WithdrawContributionAndReward {
             cooperative_name,
             token,
-        } => withdraw_contribution_and_rewards(deps, env, info, cooperative_name, token),
+            amount,
+            rewards_only,
+        } => withdraw_contribution_and_rewards(
+            deps,
+            env,
+            info,
+            cooperative_name,
+            token,
+            amount,
+            rewards_only.unwrap_or(false),
+        ),
+        ExecuteMsg::CancelWithdrawal {
+            cooperative_name,
+            token,
+            withdrawal_id,
+        } => execute_cancel_withdrawal(deps, info, cooperative_name, token, withdrawal_id),
+        ExecuteMsg::ClaimRewards {
+            cooperative_name,
+            token,
+        } => execute_claim_rewards(deps, info, cooperative_name, token),
+        ExecuteMsg::SetAutoCompound {
+            cooperative_name,
+            enabled,
+        } => execute_set_auto_compound(deps, info, cooperative_name, enabled),
         ExecuteMsg::ExecuteProposal {
             cooperative_name,
             proposal_id,
         } => execute_proposal(deps, env, info, cooperative_name, proposal_id),
+        ExecuteMsg::SettleProposalDeposit {
+            cooperative_name,
+            proposal_id,
+        } => execute_settle_proposal_deposit(deps, env, info, cooperative_name, proposal_id),
+        ExecuteMsg::CancelProposal {
+            cooperative_name,
+            proposal_id,
+        } => execute_cancel_proposal(deps, env, info, cooperative_name, proposal_id),
+        ExecuteMsg::WithdrawProtocolFees {
+            token,
+            amount,
+            recipient,
+        } => execute_withdraw_protocol_fees(deps, info, token, amount, recipient),
+        ExecuteMsg::UpdateConfig {
+            protocol_fee,
+            max_members_per_cooperative,
+            max_tokens_per_cooperative,
+            governance,
+        } => execute_update_config(
+            deps,
+            info,
+            protocol_fee,
+            max_members_per_cooperative,
+            max_tokens_per_cooperative,
+            governance,
+        ),
+        ExecuteMsg::ProposeNewOwner {
+            new_owner,
+            expires_in,
+        } => execute_propose_new_owner(deps, env, info, new_owner, expires_in),
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipTransfer {} => execute_cancel_ownership_transfer(deps, info),
+        ExecuteMsg::SetGuardian { guardian } => execute_set_guardian(deps, info, guardian),
+        ExecuteMsg::Pause {
+            cooperative_name,
+            operations,
+        } => execute_pause(deps, info, cooperative_name, operations, true),
+        ExecuteMsg::Unpause {
+            cooperative_name,
+            operations,
+        } => execute_pause(deps, info, cooperative_name, operations, false),
+        ExecuteMsg::Receive(wrapper) => execute_receive(deps, env, info, wrapper),
     }
 }
 
 pub mod execute {
-    use cosmwasm_std::{StdError, Timestamp};
-    use cw20::Cw20ExecuteMsg;
+    use cosmwasm_std::{Api, Timestamp};
+    use cw20::Cw20ReceiveMsg;
+    use cw_storage_plus::{Item, Map};
 
-    use crate::state::{Price, REWARDS_POOLS};
+    use crate::asset::{Asset, AssetInfo};
+    use crate::msg::{Cw20HookMsg, ProposalMsg};
+
+    use crate::state::{
+        CorporativeName, GovernanceParams, PausableOperation, PendingOwner, Price, ProposalData,
+        ProposalDeposit, COOPERATIVE_PAUSED, PAUSED, PENDING_OWNER, SUDO_COOPERATIVE_PAUSED,
+        SUDO_PAUSED,
+    };
+
+    // Bounds on whitelisted token metadata, subdenoms follow the Token Factory module limit
+    const MAX_DECIMALS: u8 = 18;
+    const MAX_SYMBOL_LENGTH: usize = 20;
+    const MAX_SUBDENOM_LENGTH: usize = 44;
+
+    // Queued withdrawals paid per deposit, repayment or withdrawal
+    const MAX_QUEUE_FILLS: usize = 10;
 
     use super::*;
 
-    pub fn execute_update_price(
+    pub fn execute_withdraw_protocol_fees(
         deps: DepsMut,
-        env: Env,
         info: MessageInfo,
-        token_addr: Addr,
-        usd_price: Decimal,
+        token: String,
+        amount: Option<Uint128>,
+        recipient: Option<String>,
     ) -> Result<Response, ContractError> {
         let state = STATE.load(deps.storage)?;
 
-        // only owner can update price
-        // TODO: use oracle
-        assert!(
-            state.owner == info.sender,
-            "{}",
-            ContractError::Unauthorized {}
-        );
-
-        // Get token id
-        let token_id = TOKENS.load(deps.storage, token_addr.clone())?;
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
 
-        // construct price
-        let price = Price {
-            latest_price_to_usd: usd_price.into(),
-            last_updated_at: Timestamp::from_seconds(env.block.time.seconds()),
+        let recipient = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => state.owner,
         };
 
-        // Update storage
-        PRICES.save(deps.storage, token_id, &price)?;
+        let token_id = TOKENS
+            .may_load(deps.storage, token.clone())?
+            .ok_or(ContractError::InvalidToken {})?;
+        let fees = PROTOCOL_TREASURY
+            .may_load(deps.storage, token_id)?
+            .unwrap_or_default();
+        let amount = amount.unwrap_or(fees);
+        if amount.is_zero() {
+            return Err(ContractError::NoFunds {});
+        }
+        if amount > fees {
+            return Err(ContractError::InsufficientFunds {});
+        }
+        PROTOCOL_TREASURY.save(deps.storage, token_id, &(fees - amount))?;
+
+        let token_info = AssetInfo::from_registered(&TOKEN_REGISTRY.load(deps.storage, token_id)?)?;
+        let transfer_msg = Asset::new(token_info, amount).transfer_out(&recipient)?;
 
         Ok(Response::new()
-            .add_attribute("action", "update_price")
-            .add_attribute("denom", token_addr.as_str())
-            .add_attribute("price", usd_price.to_string()))
+            .add_message(transfer_msg)
+            .add_attribute("action", "withdraw_protocol_fees")
+            .add_attribute("token", token)
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("recipient", recipient))
     }
 
-    pub fn execute_create_cooperative(
+    pub fn execute_update_config(
         deps: DepsMut,
-        name: String,
-        risk_profile: RiskProfile,
-        initial_members: Vec<Member>, //max 20 initial members
-        initial_whitelisted_tokens: Vec<WhitelistedToken>, // max 5 whitelisted tokens,
+        info: MessageInfo,
+        protocol_fee: Option<Decimal>,
+        max_members_per_cooperative: Option<u64>,
+        max_tokens_per_cooperative: Option<u64>,
+        governance: Option<GovernanceParams>,
     ) -> Result<Response, ContractError> {
-        let name = name.trim().to_lowercase();
-        // Check if cooperative already exists
-        if COOPERATIVES.has(deps.storage, name.clone()) {
-            return Err(ContractError::CooperativeAlreadyExists {});
+        let mut state = STATE.load(deps.storage)?;
+
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
         }
 
-        // Check no more than 20 initial members
-        assert!(initial_members.len().le(&20));
+        if let Some(protocol_fee) = protocol_fee {
+            state.protocol_fee = protocol_fee;
+        }
+        if let Some(max_members) = max_members_per_cooperative {
+            state.max_members_per_cooperative = max_members;
+        }
+        if let Some(max_tokens) = max_tokens_per_cooperative {
+            state.max_tokens_per_cooperative = max_tokens;
+        }
+        if let Some(governance) = governance {
+            state.governance = governance;
+        }
 
-        // Check no more than 5 whitelisted tokens.
-        assert!(initial_whitelisted_tokens.len().le(&5));
+        validate_config(&state)?;
+        STATE.save(deps.storage, &state)?;
 
-        // Create new corporative
-        let cooperative = Cooperative {
-            name: name.clone(),
-            total_funds: vec![],
-            members: initial_members,
-            risk_profile,
-            whitelisted_tokens: initial_whitelisted_tokens.clone(),
-        };
+        Ok(Response::new().add_attribute("action", "update_config"))
+    }
 
-        /* -- Update storage --- */
-        let mut state = STATE.load(deps.storage)?;
-        let current_token_idx = state.current_whitelisted_token_id + 1;
+    pub fn execute_propose_new_owner(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        new_owner: String,
+        expires_in: u64,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
 
-        // add whitelisted token to
-        for token in initial_whitelisted_tokens {
-            if !token.is_native {
-                TOKENS.save(
-                    deps.storage,
-                    token.contract_addr.clone().unwrap(),
-                    &current_token_idx,
-                )?;
-            } else {
-                TOKENS.save(deps.storage, Addr::unchecked("NATIVE"), &current_token_idx)?;
-            }
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
         }
 
-        // update total cooperative
-        state.total_corporatives += 1;
-        state.current_whitelisted_token_id = current_token_idx;
-
-        COOPERATIVES.save(deps.storage, name.clone(), &cooperative)?;
-        STATE.save(deps.storage, &state)?;
+        // The transfer must stay open for a while, and its expiry must fit in a timestamp
+        if expires_in == 0 {
+            return Err(ContractError::InvalidInput {});
+        }
+        let expires_at = env
+            .block
+            .time
+            .seconds()
+            .checked_add(expires_in)
+            .ok_or(ContractError::InvalidInput {})?;
+
+        // Replaces any transfer already in progress
+        let pending_owner = PendingOwner {
+            new_owner: deps.api.addr_validate(&new_owner)?,
+            expires_at,
+        };
+        PENDING_OWNER.save(deps.storage, &pending_owner)?;
 
         Ok(Response::new()
-            .add_attribute("action", "create_society")
-            .add_attribute("name", name))
+            .add_attribute("action", "propose_new_owner")
+            .add_attribute("new_owner", pending_owner.new_owner)
+            .add_attribute("expires_at", pending_owner.expires_at.to_string()))
     }
 
-    pub fn execute_fund_cooperative(
+    pub fn execute_accept_ownership(
         deps: DepsMut,
         env: Env,
         info: MessageInfo,
-        cooperative_name: String,
-        token: String,
-        is_native: bool,
-        amount: Uint128,
     ) -> Result<Response, ContractError> {
-        // Normalize cooperative name
-        let cooperative_name = cooperative_name.trim().to_lowercase();
-        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
-        let mut messages: Vec<CosmosMsg> = vec![];
+        let pending_owner = PENDING_OWNER
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoPendingOwner {})?;
 
-        // Validate transfer and find token index
-        let token_idx = if is_native {
-            // Validate native token
-            let coin = info
-                .funds
-                .iter()
-                .find(|coin| coin.denom == token)
-                .ok_or(ContractError::NoFunds {})?;
+        if info.sender != pending_owner.new_owner {
+            return Err(ContractError::Unauthorized {});
+        }
 
-            if coin.amount != amount {
-                return Err(ContractError::FundsMustMatchAmount {});
-            }
+        if env.block.time.seconds() >= pending_owner.expires_at {
+            return Err(ContractError::OwnershipTransferExpired {});
+        }
 
-            // Find token in whitelist
-            cooperative
-                .whitelisted_tokens
-                .iter()
-                .position(|x| x.denom == coin.denom && x.is_native)
-                .ok_or(ContractError::InvalidToken {})?
-        } else {
-            // Validate CW20 token
-            let validate_msg = validate_cw20(&deps.as_ref(), &env, &info, &token, amount)?;
-            messages = [messages, validate_msg].concat();
+        let mut state = STATE.load(deps.storage)?;
+        let previous_owner = state.owner;
+        state.owner = pending_owner.new_owner;
+        STATE.save(deps.storage, &state)?;
+        PENDING_OWNER.remove(deps.storage);
 
-            // Find token in whitelist
-            cooperative
-                .whitelisted_tokens
-                .iter()
-                .position(|x| !x.is_native && x.contract_addr == Some(Addr::unchecked(&token)))
-                .ok_or(ContractError::InvalidToken {})?
-        };
+        Ok(Response::new()
+            .add_attribute("action", "accept_ownership")
+            .add_attribute("previous_owner", previous_owner)
+            .add_attribute("owner", state.owner))
+    }
 
-        // Get the whitelisted token (we know it exists now)
-        let whitelisted_token = &cooperative.whitelisted_tokens[token_idx];
+    pub fn execute_cancel_ownership_transfer(
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
 
-        // Handle token cw20 transfer
-        if !whitelisted_token.is_native {
-            // For CW20 tokens
-            let cw20_addr = whitelisted_token
-                .contract_addr
-                .clone()
-                .ok_or(ContractError::InvalidToken {})?;
-
-            // Create CW20 transfer message
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: cw20_addr.to_string(),
-                msg: to_json_binary(&cw20::Cw20ExecuteMsg::TransferFrom {
-                    owner: info.sender.to_string(),
-                    recipient: env.contract.address.to_string(),
-                    amount,
-                })?,
-                funds: vec![],
-            }));
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
         }
 
-        // Find member
-        let member_idx = cooperative
-            .members
-            .iter()
-            .position(|m| m.address == info.sender)
-            .ok_or(ContractError::MemberNotFound {})?;
+        if !PENDING_OWNER.exists(deps.storage) {
+            return Err(ContractError::NoPendingOwner {});
+        }
+        PENDING_OWNER.remove(deps.storage);
 
-        // Update member contribution more efficiently
-        let token_id = token_idx as u64;
-        let contribution_idx = cooperative.members[member_idx]
-            .contribution
-            .iter()
-            .position(|(id, _)| *id == token_id);
+        Ok(Response::new().add_attribute("action", "cancel_ownership_transfer"))
+    }
+
+    pub fn execute_set_guardian(
+        deps: DepsMut,
+        info: MessageInfo,
+        guardian: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state = STATE.load(deps.storage)?;
+
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        state.guardian = guardian
+            .map(|guardian| deps.api.addr_validate(&guardian))
+            .transpose()?;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_guardian")
+            .add_attribute(
+                "guardian",
+                state
+                    .guardian
+                    .map(|guardian| guardian.to_string())
+                    .unwrap_or_default(),
+            ))
+    }
+
+    pub fn execute_pause(
+        deps: DepsMut,
+        info: MessageInfo,
+        cooperative_name: Option<String>,
+        operations: Option<Vec<PausableOperation>>,
+        paused: bool,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // Only the guardian or the owner can pause and unpause
+        if info.sender != state.owner && Some(&info.sender) != state.guardian.as_ref() {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let cooperative_name = cooperative_name.map(|name| name.trim().to_lowercase());
+        if let Some(name) = &cooperative_name {
+            if !COOPERATIVES.has(deps.storage, name.clone()) {
+                return Err(ContractError::CooperativeNotFound {});
+            }
+        }
+
+        let operations = operations.unwrap_or_else(|| PausableOperation::ALL.to_vec());
+        set_paused(
+            deps.storage,
+            &PAUSED,
+            &COOPERATIVE_PAUSED,
+            cooperative_name.as_deref(),
+            &operations,
+            paused,
+        )?;
+
+        Ok(Response::new()
+            .add_attribute("action", if paused { "pause" } else { "unpause" })
+            .add_attribute("cooperative", cooperative_name.unwrap_or_default())
+            .add_attribute(
+                "operations",
+                operations
+                    .iter()
+                    .map(|operation| operation.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ))
+    }
+
+    // Adds or removes operations from the global or a cooperative's paused list, kept
+    // apart for the owner and guardian and for chain governance
+    pub fn set_paused(
+        storage: &mut dyn Storage,
+        global: &Item<Vec<PausableOperation>>,
+        cooperatives: &Map<CorporativeName, Vec<PausableOperation>>,
+        cooperative_name: Option<&str>,
+        operations: &[PausableOperation],
+        paused: bool,
+    ) -> StdResult<()> {
+        let mut current = match cooperative_name {
+            Some(name) => cooperatives
+                .may_load(storage, name.to_string())?
+                .unwrap_or_default(),
+            None => global.may_load(storage)?.unwrap_or_default(),
+        };
 
-        if let Some(idx) = contribution_idx {
-            // Update existing contribution
-            cooperative.members[member_idx].contribution[idx].1 += amount;
+        if paused {
+            for operation in operations {
+                if !current.contains(operation) {
+                    current.push(*operation);
+                }
+            }
         } else {
-            // Add new contribution
-            cooperative.members[member_idx]
-                .contribution
-                .push((token_id, amount));
+            current.retain(|operation| !operations.contains(operation));
+        }
+
+        match cooperative_name {
+            Some(name) => cooperatives.save(storage, name.to_string(), &current),
+            None => global.save(storage, &current),
+        }
+    }
+
+    // Operations paused globally, and for the cooperative when given, by the owner,
+    // the guardian or chain governance
+    fn paused_operations(
+        storage: &dyn Storage,
+        cooperative_name: Option<&str>,
+    ) -> StdResult<Vec<PausableOperation>> {
+        let mut paused = PAUSED.may_load(storage)?.unwrap_or_default();
+        paused.extend(SUDO_PAUSED.may_load(storage)?.unwrap_or_default());
+
+        if let Some(name) = cooperative_name {
+            paused.extend(
+                COOPERATIVE_PAUSED
+                    .may_load(storage, name.to_string())?
+                    .unwrap_or_default(),
+            );
+            paused.extend(
+                SUDO_COOPERATIVE_PAUSED
+                    .may_load(storage, name.to_string())?
+                    .unwrap_or_default(),
+            );
+        }
+
+        Ok(paused)
+    }
+
+    // Fails when the operation is paused globally or for the cooperative
+    fn ensure_not_paused(
+        storage: &dyn Storage,
+        cooperative_name: &str,
+        operation: PausableOperation,
+    ) -> Result<(), ContractError> {
+        ensure_not_frozen(storage, cooperative_name)?;
+
+        if paused_operations(storage, Some(cooperative_name))?.contains(&operation) {
+            return Err(ContractError::Paused { operation });
         }
 
-        // Update cooperative total funds more efficiently
-        let fund_idx = cooperative
-            .total_funds
+        Ok(())
+    }
+
+    // Fails when every operation is paused globally or for the cooperative, guarding
+    // entry points that are not a pausable operation themselves
+    fn ensure_not_all_paused(
+        storage: &dyn Storage,
+        cooperative_name: Option<&str>,
+    ) -> Result<(), ContractError> {
+        let paused = paused_operations(storage, cooperative_name)?;
+        if PausableOperation::ALL
             .iter()
-            .position(|(id, _)| *id == token_id);
+            .all(|operation| paused.contains(operation))
+        {
+            return Err(ContractError::AllPaused {});
+        }
 
-        if let Some(idx) = fund_idx {
-            // Update existing fund
-            cooperative.total_funds[idx].1 += amount;
-        } else {
-            // Add new fund
-            cooperative.total_funds.push((token_id, amount));
+        Ok(())
+    }
+
+    // Fails when chain governance has frozen the cooperative
+    fn ensure_not_frozen(
+        storage: &dyn Storage,
+        cooperative_name: &str,
+    ) -> Result<(), ContractError> {
+        let frozen = COOPERATIVES
+            .may_load(storage, cooperative_name.to_string())?
+            .is_some_and(|cooperative| cooperative.frozen);
+        if frozen {
+            return Err(ContractError::CooperativeFrozen {});
         }
 
-        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+        Ok(())
+    }
+
+    pub fn execute_update_price(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        token_addr: String,
+        usd_price: Decimal,
+    ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
+
+        // only owner can update price
+        // TODO: use oracle
+        assert!(
+            state.owner == info.sender,
+            "{}",
+            ContractError::Unauthorized {}
+        );
+        ensure_not_all_paused(deps.storage, None)?;
+
+        // Get token id
+        let token_id = TOKENS.load(deps.storage, token_addr.clone())?;
+
+        // construct price
+        let price = Price {
+            latest_price_to_usd: usd_price,
+            last_updated_at: Timestamp::from_seconds(env.block.time.seconds()),
+        };
+
+        // Update storage
+        PRICES.save(deps.storage, token_id, &price)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "update_price")
+            .add_attribute("denom", token_addr)
+            .add_attribute("price", usd_price.to_string()))
+    }
+
+    pub fn execute_create_cooperative(
+        deps: DepsMut,
+        name: String,
+        risk_profile: RiskProfile,
+        initial_members: Vec<Member>,
+        initial_whitelisted_tokens: Vec<WhitelistedToken>,
+        proposal_deposit: Option<ProposalDeposit>,
+    ) -> Result<Response, ContractError> {
+        ensure_not_all_paused(deps.storage, None)?;
+
+        let name = name.trim().to_lowercase();
+        // Check if cooperative already exists
+        if COOPERATIVES.has(deps.storage, name.clone()) {
+            return Err(ContractError::CooperativeAlreadyExists {});
+        }
 
-        // Update state total pooled funds
         let mut state = STATE.load(deps.storage)?;
-        let state_token_idx = state
-            .total_pooled_funds
-            .iter()
-            .position(|(id, _)| *id == token_id);
 
-        if let Some(idx) = state_token_idx {
-            // Update existing pooled funds
-            state.total_pooled_funds[idx].1 += amount;
-        } else {
-            // Add new pooled funds
-            state.total_pooled_funds.push((token_id, amount));
+        // Check initial members and whitelisted tokens are within the configured limits
+        if initial_members.len() as u64 > state.max_members_per_cooperative {
+            return Err(ContractError::MaxMembersReached {});
+        }
+
+        if initial_whitelisted_tokens.len() as u64 > state.max_tokens_per_cooperative {
+            return Err(ContractError::MaxWhitelistedTokensReached {});
+        }
+
+        for token in &initial_whitelisted_tokens {
+            validate_whitelisted_token(deps.as_ref(), token)?;
+        }
+
+        // Create new corporative
+        let mut cooperative = Cooperative {
+            name: name.clone(),
+            total_funds: vec![],
+            total_shares: vec![],
+            total_borrowed: vec![],
+            queued_withdrawals: vec![],
+            member_count: 0,
+            risk_profile,
+            whitelisted_tokens: initial_whitelisted_tokens.clone(),
+            proposal_deposit,
+            frozen: false,
+        };
+
+        // Ensure proposal deposit is a non-zero amount of a whitelisted token
+        if let Some(deposit) = &cooperative.proposal_deposit {
+            let deposit_info = Asset::from(deposit).info;
+            if deposit.amount.is_zero()
+                || !cooperative
+                    .whitelisted_tokens
+                    .iter()
+                    .any(|x| deposit_info.matches(x))
+            {
+                return Err(ContractError::InvalidProposalDeposit {});
+            }
+        }
+
+        /* -- Update storage --- */
+        for mut member in initial_members {
+            // Balances only come from deposits
+            member.contribution = vec![];
+            member.share = vec![];
+
+            let member_key = (name.clone(), member.address.clone());
+            if COOPERATIVE_MEMBERS.has(deps.storage, member_key.clone()) {
+                return Err(ContractError::AlreadyMember {});
+            }
+
+            // Update member's cooperative list
+            let mut member_coops = MEMBERS
+                .may_load(deps.storage, member.address.clone())?
+                .unwrap_or_default();
+            member_coops.push(name.clone());
+            MEMBERS.save(deps.storage, member.address.clone(), &member_coops)?;
+
+            COOPERATIVE_MEMBERS.save(deps.storage, member_key, &member)?;
+            cooperative.member_count += 1;
+        }
+
+        // Register whitelisted tokens, rejecting duplicates
+        let mut token_ids: Vec<WhitelistedTokenId> = vec![];
+        for token in &initial_whitelisted_tokens {
+            let token_id = register_token(deps.storage, &mut state, token)?;
+            if token_ids.contains(&token_id) {
+                return Err(ContractError::TokenAlreadyWhitelisted {});
+            }
+            token_ids.push(token_id);
         }
 
+        // update total cooperative
+        state.total_corporatives += 1;
+
+        COOPERATIVES.save(deps.storage, name.clone(), &cooperative)?;
         STATE.save(deps.storage, &state)?;
 
         Ok(Response::new()
-            .add_messages(messages)
+            .add_attribute("action", "create_society")
+            .add_attribute("name", name))
+    }
+
+    pub fn execute_fund_cooperative(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        cooperative_name: String,
+        token: String,
+        is_native: bool,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        // Normalize cooperative name
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Fund)?;
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+
+        // Find token in whitelist
+        let token_info = if is_native {
+            AssetInfo::Native {
+                denom: token.clone(),
+            }
+        } else {
+            AssetInfo::Cw20 {
+                contract_addr: deps.api.addr_validate(&token)?,
+            }
+        };
+        if !cooperative
+            .whitelisted_tokens
+            .iter()
+            .any(|x| token_info.matches(x))
+        {
+            return Err(ContractError::InvalidToken {});
+        }
+        let token_id = token_info.token_id(deps.storage)?;
+
+        let transfer_msg =
+            Asset::new(token_info.clone(), amount).transfer_in(&deps.querier, &env, &info)?;
+
+        let shares = record_contribution(
+            deps.storage,
+            &cooperative_name,
+            &info.sender,
+            token_id,
+            amount,
+        )?;
+        let (fill_msgs, filled) =
+            fill_withdrawal_queue(deps.storage, &cooperative_name, &token_info)?;
+
+        Ok(Response::new()
+            .add_messages(transfer_msg)
+            .add_messages(fill_msgs)
             .add_attribute("action", "fund_cooperative")
             .add_attribute("cooperative", cooperative_name)
             .add_attribute("token", token)
-            .add_attribute("amount", amount.to_string()))
-    }
-
-    fn validate_cw20(
-        deps: &Deps,
-        env: &Env,
-        info: &MessageInfo,
-        token_address: &str,
-        required_amount: Uint128,
-    ) -> StdResult<Vec<CosmosMsg>> {
-        // Query token balance
-        let balance: cw20::BalanceResponse = deps.querier.query_wasm_smart(
-            token_address,
-            &cw20::Cw20QueryMsg::Balance {
-                address: info.sender.to_string(),
-            },
-        )?;
+            .add_attribute("amount", amount.to_string())
+            .add_attribute("shares", shares.to_string())
+            .add_attribute("queue_filled", filled.to_string()))
+    }
 
-        // Check if user has sufficient balance
-        if balance.balance < required_amount {
-            return Err(StdError::generic_err(format!(
-                "Insufficient CW20 token balance. Required: {}, Balance: {}",
-                required_amount, balance.balance
-            )));
-        }
+    // Mints pool shares for a member deposit at the current exchange rate, and credits the
+    // deposit to the cooperative and protocol totals
+    fn record_contribution(
+        storage: &mut dyn Storage,
+        cooperative_name: &str,
+        member_addr: &Addr,
+        token_id: WhitelistedTokenId,
+        amount: Uint128,
+    ) -> Result<Uint128, ContractError> {
+        let mut cooperative = COOPERATIVES.load(storage, cooperative_name.to_string())?;
 
-        // Query allowance
-        let allowance: cw20::AllowanceResponse = deps.querier.query_wasm_smart(
-            token_address,
-            &cw20::Cw20QueryMsg::Allowance {
-                owner: info.sender.to_string(),
-                spender: env.contract.address.to_string(),
-            },
+        // Find member
+        let member_key = (cooperative_name.to_string(), member_addr.clone());
+        let mut member = COOPERATIVE_MEMBERS
+            .may_load(storage, member_key.clone())?
+            .ok_or(ContractError::MemberNotFound {})?;
+
+        settle_rewards(
+            storage,
+            cooperative_name,
+            &mut cooperative,
+            &mut member,
+            token_id,
         )?;
 
-        // Check if contract has sufficient allowance
-        if allowance.allowance < required_amount {
-            return Err(StdError::generic_err(format!(
-                "Insufficient CW20 token allowance. Required: {}, Allowance: {}",
-                required_amount, allowance.allowance
-            )));
+        let shares = shares_for(&cooperative, token_id, amount);
+        if shares.is_zero() {
+            return Err(ContractError::InvalidFundAmount {});
         }
 
-        // move funds to contract
-        let mut messages: Vec<CosmosMsg> = vec![];
-        messages.push(
-            WasmMsg::Execute {
-                contract_addr: token_address.to_string(),
-                msg: to_json_binary(&cw20::Cw20ExecuteMsg::TransferFrom {
-                    owner: info.sender.to_string(),
-                    recipient: env.contract.address.to_string(),
-                    amount: required_amount,
-                })?,
-                funds: vec![],
-            }
-            .into(),
-        );
+        add_amount(&mut member.contribution, token_id, amount);
+        add_amount(&mut member.share, token_id, shares);
+        add_amount(&mut cooperative.total_funds, token_id, amount);
+        add_amount(&mut cooperative.total_shares, token_id, shares);
 
-        Ok(messages)
+        COOPERATIVE_MEMBERS.save(storage, member_key, &member)?;
+        COOPERATIVES.save(storage, cooperative_name.to_string(), &cooperative)?;
+
+        // Update state total pooled funds
+        let mut state = STATE.load(storage)?;
+        add_amount(&mut state.total_pooled_funds, token_id, amount);
+        STATE.save(storage, &state)?;
+
+        Ok(shares)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn execute_borrow(
         deps: DepsMut,
-        env: Env,
+        _env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        tokens_in: Vec<Addr>,
+        tokens_in: Vec<String>,
         amount_in: Vec<Uint128>,
-        token_out: Addr,
+        token_out: String,
         min_amount_out: Uint128,
     ) -> Result<Response, ContractError> {
         // Validate input arrays
@@ -434,71 +1158,84 @@ pub mod execute {
 
         // Normalize cooperative name
         let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Borrow)?;
         let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
         let mut state = STATE.load(deps.storage)?;
         let loan_id = state.current_loan_id;
 
         // Find member
-        let member_idx = cooperative
-            .members
-            .iter()
-            .position(|m| m.address == info.sender)
+        let member = COOPERATIVE_MEMBERS
+            .may_load(
+                deps.storage,
+                (cooperative_name.clone(), info.sender.clone()),
+            )?
             .ok_or(ContractError::MemberNotFound {})?;
 
         // Validate output token
-        let w_token_idx = cooperative
-            .whitelisted_tokens
-            .iter()
-            .position(|x| x.contract_addr == Some(token_out.clone()))
-            .ok_or(ContractError::InvalidToken {})?;
-
-        let w_token = &cooperative.whitelisted_tokens[w_token_idx];
+        let (w_token_out, token_out_info) = resolve_whitelisted(&cooperative, &token_out)?;
 
         // Calculate collateral value and amount out
-        let mut collateral_value: Uint128 = Uint128::zero();
+        let mut collateral_value = Decimal256::zero();
         let mut collateral_details: Vec<(u64, Uint128)> = Vec::new();
-        let mut messages: Vec<CosmosMsg> = vec![];
+        let mut collaterals: Vec<String> = vec![];
+        let mut collateral_shares: Vec<Uint128> = vec![];
 
         // Process each input token
         for (idx, token) in tokens_in.iter().enumerate() {
-            let token_id = TOKENS.load(deps.storage, token.clone())?;
+            let (w_token, token_info) = resolve_whitelisted(&cooperative, token)?;
+            let token_id = token_info.token_id(deps.storage)?;
+            if collaterals.contains(&token_info.to_string()) {
+                return Err(ContractError::InvalidInput {});
+            }
+            collaterals.push(token_info.to_string());
 
-            // Find token in member's contributions
-            let contribution_idx = cooperative.members[member_idx]
-                .contribution
-                .iter()
-                .position(|x| x.0 == token_id)
-                .ok_or(ContractError::NoContribution {})?;
+            // Find token in member's pool shares
+            let member_shares = amount_of(&member.share, token_id);
+            if member_shares.is_zero() {
+                return Err(ContractError::NoContribution {});
+            }
 
-            let available_amount = cooperative.members[member_idx].contribution[contribution_idx].1;
+            // Lock enough of the member's free shares to cover the requested amount
             let requested_amount = amount_in[idx];
+            let shares = shares_to_cover(&cooperative, token_id, requested_amount);
 
-            // Ensure member has enough of this token
-            if available_amount < requested_amount {
+            let locked = locked_shares(
+                deps.storage,
+                &cooperative_name,
+                &info.sender,
+                &token_info.to_string(),
+            )?;
+            if member_shares.saturating_sub(locked) < shares {
                 return Err(ContractError::InsufficientFunds {});
             }
+            collateral_shares.push(shares);
 
             // Get token price for valuation
             let token_price = PRICES.load(deps.storage, token_id)?;
-            let token_value = requested_amount * token_price.latest_price_to_usd.to_uint_floor();
+            let token_value = usd_value(
+                requested_amount,
+                w_token.decimals,
+                token_price.latest_price_to_usd,
+            )?;
 
             collateral_value += token_value;
             collateral_details.push((token_id, requested_amount));
-
-            // Reduce member's contribution
-            cooperative.members[member_idx].contribution[contribution_idx].1 -= requested_amount;
         }
 
         // Calculate amount out based on collateral value and risk profile
-        let token_out_id = TOKENS.load(deps.storage, token_out.clone())?;
+        let token_out_id = token_out_info.token_id(deps.storage)?;
         let token_out_price = PRICES.load(deps.storage, token_out_id)?;
 
         let loan_value = collateral_value
-            * cooperative
-                .risk_profile
-                .collateralization_ratio
-                .to_uint_floor();
-        let amount_out = loan_value / token_out_price.latest_price_to_usd.to_uint_floor();
+            .checked_mul(Decimal256::from(
+                cooperative.risk_profile.collateralization_ratio,
+            ))
+            .map_err(cosmwasm_std::StdError::from)?;
+        let amount_out = token_amount(
+            loan_value,
+            w_token_out.decimals,
+            token_out_price.latest_price_to_usd,
+        )?;
 
         // Ensure minimum amount out is met
         if amount_out < min_amount_out {
@@ -509,75 +1246,34 @@ pub mod execute {
         let interest_rate = cooperative.risk_profile.interest_rate.to_uint_ceil().u128() as u64;
         let loan = Loan {
             id: loan_id,
+            cooperative: cooperative_name.clone(),
+            borrower: info.sender.clone(),
             amount: amount_out,
-            token: token_out.clone(),
-            collaterals: tokens_in.clone(),
+            token: token_out_info.to_string(),
+            collaterals,
             collaterals_amount: amount_in.clone(),
+            collateral_shares,
             interest_rate: Decimal::percent(interest_rate),
             status: LoanStatus::Active,
         };
 
-        // Handle token transfer based on type
-        if w_token.is_native {
-            // Check if the contract has enough funds
-            let contract_balance = deps
-                .querier
-                .query_balance(env.contract.address.clone(), w_token.denom.clone())?;
-
-            if contract_balance.amount < amount_out {
-                return Err(ContractError::InsufficientPoolFunds {});
-            }
-
-            // For native tokens
-            messages.push(CosmosMsg::Bank(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: vec![Coin {
-                    denom: w_token.denom.clone(),
-                    amount: amount_out,
-                }],
-            }));
-        } else {
-            // For CW20 tokens
-            let cw20_addr = w_token
-                .contract_addr
-                .clone()
-                .ok_or(ContractError::InvalidToken {})?;
-
-            // Check contract balance
-            let balance: cw20::BalanceResponse = deps.querier.query_wasm_smart(
-                cw20_addr.clone(),
-                &cw20::Cw20QueryMsg::Balance {
-                    address: env.contract.address.to_string(),
-                },
-            )?;
-
-            if balance.balance < amount_out {
-                return Err(ContractError::InsufficientPoolFunds {});
-            }
-
-            // Create CW20 transfer message
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: cw20_addr.to_string(),
-                msg: to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
-                    recipient: info.sender.to_string(),
-                    amount: amount_out,
-                })?,
-                funds: vec![],
-            }));
+        // Only funds not lent out or owed to queued withdrawals can be borrowed
+        if available_liquidity(&cooperative, token_out_id) < amount_out {
+            return Err(ContractError::InsufficientPoolFunds {});
         }
-
-        // Update member data
-        cooperative.members[member_idx].loans.push(loan);
+        let transfer_msg = Asset::new(token_out_info, amount_out).transfer_out(&info.sender)?;
 
         // Update state
         state.current_loan_id += 1;
+        add_amount(&mut cooperative.total_borrowed, token_out_id, amount_out);
 
         // Save updates
+        LOANS.save(deps.storage, loan_id, &loan)?;
         COOPERATIVES.save(deps.storage, cooperative_name, &cooperative)?;
         STATE.save(deps.storage, &state)?;
 
         Ok(Response::new()
-            .add_messages(messages)
+            .add_message(transfer_msg)
             .add_attribute("action", "borrow")
             .add_attribute("loan_id", loan_id.to_string())
             .add_attribute("borrower", info.sender.to_string())
@@ -591,882 +1287,3177 @@ pub mod execute {
         env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        token: Addr,
+        token: String,
     ) -> Result<Response, ContractError> {
         // Normalize cooperative name
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Repay)?;
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let (_, token_info) = resolve_whitelisted(&cooperative, &token)?;
 
         // Find member
-        let member_idx = cooperative
-            .members
-            .iter()
-            .position(|m| m.address == info.sender)
-            .ok_or(ContractError::MemberNotFound {})?;
+        let member_key = (cooperative_name.clone(), info.sender.clone());
+        if !COOPERATIVE_MEMBERS.has(deps.storage, member_key) {
+            return Err(ContractError::MemberNotFound {});
+        }
 
         // Find active loan (for given token)
-        let loan_idx = cooperative.members[member_idx]
-            .loans
-            .iter()
-            .position(|l| l.status == LoanStatus::Active && l.token == token)
+        let borrower_loans = LOANS
+            .idx
+            .borrower
+            .prefix(info.sender.clone())
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut loan = borrower_loans
+            .into_iter()
+            .map(|(_, loan)| loan)
+            .find(|l| {
+                l.cooperative == cooperative_name
+                    && l.status == LoanStatus::Active
+                    && l.token == token_info.to_string()
+            })
             .ok_or(ContractError::NoActiveLoan {})?;
 
-        let loan = cooperative.members[member_idx].loans[loan_idx].clone();
-        let repayment_amount = loan.amount;
+        let repayment_amount = loan.amount_due();
 
-        // Find token in whitelist
-        let w_token_idx = cooperative
-            .whitelisted_tokens
-            .iter()
-            .position(|x| x.contract_addr == Some(token.clone()))
-            .ok_or(ContractError::InvalidToken {})?;
+        // Take the repayment
+        let repayment = Asset::new(token_info.clone(), repayment_amount);
+        let transfer_msg = repayment.transfer_in(&deps.querier, &env, &info)?;
 
-        let w_token = &cooperative.whitelisted_tokens[w_token_idx];
+        let fee = settle_loan(deps.storage, &mut loan)?;
+        let (fill_msgs, filled) =
+            fill_withdrawal_queue(deps.storage, &cooperative_name, &token_info)?;
 
-        // Validate payment
-        if w_token.is_native {
-            // For native tokens, verify sent funds
-            let sent_funds = info
-                .funds
-                .iter()
-                .find(|coin| coin.denom == w_token.denom)
-                .ok_or(ContractError::NoFunds {})?;
+        Ok(Response::new()
+            .add_messages(transfer_msg)
+            .add_messages(fill_msgs)
+            .add_attribute("action", "repay")
+            .add_attribute("loan_id", loan.id.to_string())
+            .add_attribute("borrower", info.sender.to_string())
+            .add_attribute("token", token)
+            .add_attribute("amount", repayment_amount.to_string())
+            .add_attribute("interest", loan.interest().to_string())
+            .add_attribute("protocol_fee", fee.to_string())
+            .add_attribute("queue_filled", filled.to_string()))
+    }
 
-            if sent_funds.amount != repayment_amount {
-                return Err(ContractError::InvalidFundAmount {});
-            }
-        } else {
-            // For CW20 tokens, validate allowance first
-            let cw20_addr = w_token
-                .contract_addr
-                .clone()
-                .ok_or(ContractError::InvalidToken {})?;
-
-            // Check allowance
-            let allowance: cw20::AllowanceResponse = deps.querier.query_wasm_smart(
-                cw20_addr.clone(),
-                &cw20::Cw20QueryMsg::Allowance {
-                    owner: info.sender.to_string(),
-                    spender: env.contract.address.to_string(),
-                },
-            )?;
+    // Returns the principal to the lending pool and credits the interest, raising its exchange
+    // rate, and marks the loan repaid which releases the locked collateral shares
+    fn settle_loan(storage: &mut dyn Storage, loan: &mut Loan) -> Result<Uint128, ContractError> {
+        let token_id = TOKENS.load(storage, loan.token.clone())?;
+        let mut state = STATE.load(storage)?;
 
-            if allowance.allowance < repayment_amount {
-                return Err(ContractError::InsufficientAllowance {});
-            }
-        }
+        // The protocol keeps its fee out of the interest
+        let interest = loan.interest();
+        let fee = interest.mul_floor(state.protocol_fee);
+        let pool_interest = interest - fee;
 
-        // Return collaterals to the member's contribution
-        let collaterals = &loan.collaterals;
-        let collateral_amounts = &loan.collaterals_amount;
+        let mut cooperative = COOPERATIVES.load(storage, loan.cooperative.clone())?;
+        sub_amount(&mut cooperative.total_borrowed, token_id, loan.amount)?;
+        add_amount(&mut cooperative.total_funds, token_id, pool_interest);
+        COOPERATIVES.save(storage, loan.cooperative.clone(), &cooperative)?;
 
-        // Here's the fixed version of your loan repayment code with proper bounds checking
-        for (idx, collateral) in collaterals.iter().enumerate() {
-            // Add bounds check to prevent index out of bounds error
-            if idx >= collateral_amounts.len() {
-                return Err(ContractError::InvalidCollateral {
-                    msg: format!("Collateral amount missing for collateral at index {}", idx),
-                });
-            }
+        add_amount(&mut state.total_pooled_funds, token_id, pool_interest);
+        STATE.save(storage, &state)?;
 
-            let token_id = TOKENS.load(deps.storage, collateral.clone())?;
+        if !fee.is_zero() {
+            PROTOCOL_TREASURY.update(storage, token_id, |fees| -> StdResult<_> {
+                Ok(fees.unwrap_or_default() + fee)
+            })?;
+        }
 
-            // Find or create contribution entry
-            let contribution_idx = cooperative.members[member_idx]
-                .contribution
-                .iter()
-                .position(|x| x.0 == token_id);
+        // Mark loan as repaid
+        loan.status = LoanStatus::Repaid;
+        LOANS.save(storage, loan.id, loan)?;
 
-            if let Some(contrib_idx) = contribution_idx {
-                // Update existing contribution
-                cooperative.members[member_idx].contribution[contrib_idx].1 +=
-                    collateral_amounts[idx];
-            } else {
-                // Add new contribution
-                cooperative.members[member_idx]
-                    .contribution
-                    .push((token_id, collateral_amounts[idx]));
+        Ok(fee)
+    }
+
+    // Pool shares of a member locked as collateral by their active loans in a cooperative
+    fn locked_shares(
+        storage: &dyn Storage,
+        cooperative_name: &str,
+        borrower: &Addr,
+        token: &str,
+    ) -> StdResult<Uint128> {
+        let loans = LOANS
+            .idx
+            .borrower
+            .prefix(borrower.clone())
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(loans
+            .iter()
+            .filter(|(_, l)| l.cooperative == cooperative_name && l.status == LoanStatus::Active)
+            .flat_map(|(_, l)| l.collaterals.iter().zip(&l.collateral_shares))
+            .filter(|(collateral, _)| collateral.as_str() == token)
+            .map(|(_, shares)| *shares)
+            .sum())
+    }
+
+    // Handles tokens sent with a CW20 `Send`, the CW20 contract being the caller
+    pub fn execute_receive(
+        deps: DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        wrapper: Cw20ReceiveMsg,
+    ) -> Result<Response, ContractError> {
+        let sender = deps.api.addr_validate(&wrapper.sender)?;
+        let token = info.sender;
+        let token_info = AssetInfo::Cw20 {
+            contract_addr: token.clone(),
+        };
+        let amount = wrapper.amount;
+
+        if amount.is_zero() {
+            return Err(ContractError::NoFunds {});
+        }
+
+        match from_json(&wrapper.msg)? {
+            Cw20HookMsg::Fund { cooperative_name } => {
+                let cooperative_name = cooperative_name.trim().to_lowercase();
+                ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Fund)?;
+                let token_id = cw20_token_id(deps.storage, &cooperative_name, &token)?;
+
+                let shares = record_contribution(
+                    deps.storage,
+                    &cooperative_name,
+                    &sender,
+                    token_id,
+                    amount,
+                )?;
+                let (fill_msgs, filled) =
+                    fill_withdrawal_queue(deps.storage, &cooperative_name, &token_info)?;
+
+                Ok(Response::new()
+                    .add_messages(fill_msgs)
+                    .add_attribute("action", "fund_cooperative")
+                    .add_attribute("cooperative", cooperative_name)
+                    .add_attribute("token", token)
+                    .add_attribute("amount", amount.to_string())
+                    .add_attribute("shares", shares.to_string())
+                    .add_attribute("queue_filled", filled.to_string()))
+            }
+            Cw20HookMsg::Repay {
+                cooperative_name,
+                loan_id,
+            } => {
+                let cooperative_name = cooperative_name.trim().to_lowercase();
+                ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Repay)?;
+                cw20_token_id(deps.storage, &cooperative_name, &token)?;
+
+                let mut loan = LOANS
+                    .may_load(deps.storage, loan_id)?
+                    .ok_or(ContractError::NoActiveLoan {})?;
+
+                if loan.cooperative != cooperative_name
+                    || loan.borrower != sender
+                    || loan.status != LoanStatus::Active
+                {
+                    return Err(ContractError::NoActiveLoan {});
+                }
+
+                if loan.token != token.as_str() {
+                    return Err(ContractError::InvalidToken {});
+                }
+
+                if amount != loan.amount_due() {
+                    return Err(ContractError::InvalidFundAmount {});
+                }
+
+                let fee = settle_loan(deps.storage, &mut loan)?;
+                let (fill_msgs, filled) =
+                    fill_withdrawal_queue(deps.storage, &cooperative_name, &token_info)?;
+
+                Ok(Response::new()
+                    .add_messages(fill_msgs)
+                    .add_attribute("action", "repay")
+                    .add_attribute("loan_id", loan.id.to_string())
+                    .add_attribute("borrower", sender)
+                    .add_attribute("token", token)
+                    .add_attribute("amount", amount.to_string())
+                    .add_attribute("interest", loan.interest().to_string())
+                    .add_attribute("protocol_fee", fee.to_string())
+                    .add_attribute("queue_filled", filled.to_string()))
+            }
+            Cw20HookMsg::Contribute { cooperative_name } => {
+                let cooperative_name = cooperative_name.trim().to_lowercase();
+                ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Fund)?;
+                let token_id = cw20_token_id(deps.storage, &cooperative_name, &token)?;
+
+                let pool_key = (cooperative_name.clone(), token_id);
+                let mut pool = REWARDS_POOLS
+                    .may_load(deps.storage, pool_key.clone())?
+                    .unwrap_or(CooperativeRewardsPool {
+                        cooperative_name: cooperative_name.clone(),
+                        token_id,
+                        total_rewards: Uint128::zero(),
+                        distributed_rewards: Uint128::zero(),
+                        indexed_rewards: Uint128::zero(),
+                        reward_per_share: Decimal256::zero(),
+                    });
+                pool.total_rewards += amount;
+                REWARDS_POOLS.save(deps.storage, pool_key, &pool)?;
+
+                Ok(Response::new()
+                    .add_attribute("action", "contribute_rewards")
+                    .add_attribute("cooperative", cooperative_name)
+                    .add_attribute("contributor", sender)
+                    .add_attribute("token", token)
+                    .add_attribute("amount", amount.to_string()))
             }
         }
+    }
 
-        // Mark loan as repaid
-        cooperative.members[member_idx].loans[loan_idx].status = LoanStatus::Repaid;
+    // Id of a CW20 token whitelisted by the cooperative
+    fn cw20_token_id(
+        storage: &dyn Storage,
+        cooperative_name: &str,
+        token: &Addr,
+    ) -> Result<WhitelistedTokenId, ContractError> {
+        let cooperative = COOPERATIVES.load(storage, cooperative_name.to_string())?;
 
-        // Handle token transfer based on type
-        let mut messages: Vec<CosmosMsg> = vec![];
+        let token_info = AssetInfo::Cw20 {
+            contract_addr: token.clone(),
+        };
+        if !cooperative
+            .whitelisted_tokens
+            .iter()
+            .any(|x| token_info.matches(x))
+        {
+            return Err(ContractError::InvalidToken {});
+        }
+
+        token_info.token_id(storage)
+    }
+
+    // Resolves a native denom or CW20 address against the cooperative whitelist
+    fn resolve_whitelisted<'a>(
+        cooperative: &'a Cooperative,
+        token: &str,
+    ) -> Result<(&'a WhitelistedToken, AssetInfo), ContractError> {
+        let w_token = cooperative
+            .whitelisted_tokens
+            .iter()
+            .find(|x| match &x.contract_addr {
+                Some(contract_addr) if !x.is_native => contract_addr.as_str() == token,
+                _ => x.is_native && x.denom == token,
+            })
+            .ok_or(ContractError::InvalidToken {})?;
+
+        Ok((w_token, AssetInfo::from_whitelisted(w_token)?))
+    }
+
+    // Token described by a whitelist proposal
+    fn proposed_token(data: &ProposalData) -> Result<WhitelistedToken, ContractError> {
+        let is_native = data.is_native.ok_or(ContractError::InvalidProposal {})?;
+        if !is_native && data.token_addr.is_none() {
+            return Err(ContractError::InvalidProposal {});
+        }
+
+        Ok(WhitelistedToken {
+            denom: data
+                .denom
+                .clone()
+                .ok_or(ContractError::InvalidProposal {})?,
+            contract_addr: data.token_addr.clone(),
+            is_native,
+            max_loan_ratio: data
+                .max_loan_ratio
+                .ok_or(ContractError::InvalidProposal {})?,
+            decimals: data.decimals.ok_or(ContractError::InvalidProposal {})?,
+            symbol: data
+                .symbol
+                .clone()
+                .ok_or(ContractError::InvalidProposal {})?,
+        })
+    }
 
-        if !w_token.is_native {
-            // For CW20 tokens
-            let cw20_addr = w_token
+    // Native tokens must use a valid `ibc/...`, `factory/...` or base denom, CW20 tokens a
+    // CW20 contract whose token info reports the declared decimals.
+    fn validate_whitelisted_token(
+        deps: Deps,
+        token: &WhitelistedToken,
+    ) -> Result<(), ContractError> {
+        let invalid = |msg: String| ContractError::InvalidWhitelistedToken { msg };
+
+        if token.is_native {
+            if token.contract_addr.is_some() {
+                return Err(invalid(
+                    "native token cannot have a contract address".into(),
+                ));
+            }
+            validate_denom(deps.api, &token.denom).map_err(invalid)?;
+        } else {
+            let contract_addr = token
                 .contract_addr
                 .clone()
-                .ok_or(ContractError::InvalidToken {})?;
+                .ok_or_else(|| invalid("CW20 token requires a contract address".into()))?;
+            deps.api.addr_validate(contract_addr.as_str())?;
+
+            let token_info = AssetInfo::Cw20 { contract_addr };
+            if let Some(decimals) = token_info.decimals(&deps.querier)? {
+                if decimals != token.decimals {
+                    return Err(invalid(format!(
+                        "decimals {} differ from the token's {}",
+                        token.decimals, decimals
+                    )));
+                }
+            }
+        }
 
-            // Create CW20 transferFrom message
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: cw20_addr.to_string(),
-                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
-                    owner: info.sender.to_string(),
-                    recipient: env.contract.address.to_string(),
-                    amount: repayment_amount,
-                })?,
-                funds: vec![],
-            }));
+        if token.decimals > MAX_DECIMALS {
+            return Err(invalid(format!("decimals cannot exceed {}", MAX_DECIMALS)));
         }
 
-        // Save updated cooperative data
-        COOPERATIVES.save(deps.storage, cooperative_name, &cooperative)?;
+        if token.symbol.is_empty()
+            || token.symbol.len() > MAX_SYMBOL_LENGTH
+            || !token
+                .symbol
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".-_".contains(c))
+        {
+            return Err(invalid(format!("invalid symbol {}", token.symbol)));
+        }
 
-        Ok(Response::new()
-            .add_messages(messages)
-            .add_attribute("action", "repay")
-            .add_attribute("loan_id", loan.id.to_string())
-            .add_attribute("borrower", info.sender.to_string())
-            .add_attribute("token", token.to_string())
-            .add_attribute("amount", repayment_amount.to_string()))
+        Ok(())
+    }
+
+    fn validate_denom(api: &dyn Api, denom: &str) -> Result<(), String> {
+        let invalid = || format!("invalid denom {}", denom);
+
+        // IBC denoms are the uppercase hex SHA-256 hash of the denom trace
+        if let Some(hash) = denom.strip_prefix("ibc/") {
+            if hash.len() != 64 || !hash.chars().all(|c| matches!(c, '0'..='9' | 'A'..='F')) {
+                return Err(invalid());
+            }
+            return Ok(());
+        }
+
+        // Token Factory denoms are `factory/{creator}/{subdenom}`
+        if let Some(path) = denom.strip_prefix("factory/") {
+            let (creator, subdenom) = path.split_once('/').ok_or_else(invalid)?;
+            api.addr_validate(creator).map_err(|_| invalid())?;
+            if subdenom.is_empty()
+                || subdenom.len() > MAX_SUBDENOM_LENGTH
+                || !subdenom
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "/._-".contains(c))
+            {
+                return Err(invalid());
+            }
+            return Ok(());
+        }
+
+        // Base denoms are 3 to 128 characters, starting with a letter
+        if denom.len() < 3
+            || denom.len() > 128
+            || !denom.starts_with(|c: char| c.is_ascii_alphabetic())
+            || !denom
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "/:._-".contains(c))
+        {
+            return Err(invalid());
+        }
+
+        Ok(())
+    }
+
+    // USD value of a raw token amount. Amounts are scaled down by the token decimals first,
+    // so tokens with 6 and 18 decimals are valued at the same 18 decimal precision.
+    fn usd_value(amount: Uint128, decimals: u8, price: Decimal) -> StdResult<Decimal256> {
+        let units = Decimal256::from_atomics(amount, decimals.into())
+            .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))?;
+        Ok(units.checked_mul(Decimal256::from(price))?)
+    }
+
+    // Raw amount of a token worth `value` USD, rounded down
+    fn token_amount(value: Decimal256, decimals: u8, price: Decimal) -> StdResult<Uint128> {
+        let units = value
+            .checked_div(Decimal256::from(price))
+            .map_err(|e| cosmwasm_std::StdError::generic_err(e.to_string()))?;
+        let scale = Uint256::from(10u8).pow(Decimal256::DECIMAL_PLACES - u32::from(decimals));
+        Ok(Uint128::try_from(units.atomics().checked_div(scale)?)?)
+    }
+
+    // Assigns a registry id to a token the first time any cooperative whitelists it. Later
+    // whitelistings must agree on the decimals, since all cooperatives share one price.
+    pub(crate) fn register_token(
+        storage: &mut dyn Storage,
+        state: &mut State,
+        token: &WhitelistedToken,
+    ) -> Result<WhitelistedTokenId, ContractError> {
+        let token_info = AssetInfo::from_whitelisted(token)?;
+        if let Some(token_id) = TOKENS.may_load(storage, token_info.to_string())? {
+            let registered = TOKEN_REGISTRY.load(storage, token_id)?;
+            if registered.decimals != token.decimals {
+                return Err(ContractError::InvalidWhitelistedToken {
+                    msg: format!(
+                        "decimals {} differ from the registered {}",
+                        token.decimals, registered.decimals
+                    ),
+                });
+            }
+            return Ok(token_id);
+        }
+
+        let token_id = state.current_whitelisted_token_id + 1;
+        state.current_whitelisted_token_id = token_id;
+
+        TOKENS.save(storage, token_info.to_string(), &token_id)?;
+        TOKEN_REGISTRY.save(
+            storage,
+            token_id,
+            &RegisteredToken {
+                id: token_id,
+                denom: token.denom.clone(),
+                contract_addr: token.contract_addr.clone(),
+                is_native: token.is_native,
+                decimals: token.decimals,
+                symbol: token.symbol.clone(),
+            },
+        )?;
+
+        Ok(token_id)
     }
 
     pub fn withdraw_contribution_and_rewards(
         deps: DepsMut,
-        _env: Env,
+        env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        token: Addr,
+        token: String,
+        amount: Option<Uint128>,
+        rewards_only: bool,
     ) -> Result<Response, ContractError> {
         // Normalize cooperative name
         let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Withdraw)?;
         let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
 
         // Find member
-        let member_idx = cooperative
-            .members
-            .iter()
-            .position(|m| m.address == info.sender)
+        let member_key = (cooperative_name.clone(), info.sender.clone());
+        let mut member = COOPERATIVE_MEMBERS
+            .may_load(deps.storage, member_key.clone())?
             .ok_or(ContractError::MemberNotFound {})?;
 
         // Find token in whitelist
-        let w_token_idx = cooperative
-            .whitelisted_tokens
-            .iter()
-            .position(|x| x.contract_addr == Some(token.clone()))
-            .ok_or(ContractError::InvalidToken {})?;
+        let (_, token_info) = resolve_whitelisted(&cooperative, &token)?;
+        let token_id = token_info.token_id(deps.storage)?;
 
-        let w_token = &cooperative.whitelisted_tokens[w_token_idx];
-
-        // Find member's contribution for this token
-        let contribution_idx = cooperative.members[member_idx]
-            .contribution
-            .iter()
-            .position(|x| x.0 == w_token_idx as u64)
-            .ok_or(ContractError::NoContribution {})?;
+        // Settle rewards first, auto-compounding adds to the shares being withdrawn
+        let member_rewards = settle_rewards(
+            deps.storage,
+            &cooperative_name,
+            &mut cooperative,
+            &mut member,
+            token_id,
+        )?;
 
-        // Get the amount to withdraw
-        let amount = cooperative.members[member_idx].contribution[contribution_idx].1;
-        if amount.is_zero() {
+        let member_shares = amount_of(&member.share, token_id);
+        if member_shares.is_zero() {
             return Err(ContractError::NoContribution {});
         }
 
-        // Check if the total funds available in the cooperative are sufficient
-        let total_available_funds = cooperative
-            .total_funds
-            .iter()
-            .find(|(id, _)| *id == w_token_idx as u64)
-            .map(|(_, amount)| *amount)
-            .unwrap_or(Uint128::zero());
-
-        if total_available_funds < amount {
-            return Err(ContractError::InsufficientPoolFunds {});
+        // Shares locked as collateral cannot be withdrawn
+        let locked = locked_shares(
+            deps.storage,
+            &cooperative_name,
+            &info.sender,
+            &token_info.to_string(),
+        )?;
+        let free_shares = member_shares.saturating_sub(locked);
+        let contribution = amount_of(&member.contribution, token_id);
+
+        // Shares to burn, and the principal they take out in proportion to the member's shares
+        let shares = match (amount, rewards_only) {
+            (Some(_), true) => return Err(ContractError::InvalidInput {}),
+            (Some(amount), false) if amount.is_zero() => {
+                return Err(ContractError::InvalidInput {})
+            }
+            (Some(amount), false) => shares_to_cover(&cooperative, token_id, amount),
+            (None, false) => free_shares,
+            (None, true) => {
+                let earned = shares_value(&cooperative, token_id, member_shares)
+                    .saturating_sub(contribution);
+                let shares = shares_for(&cooperative, token_id, earned);
+                if shares.is_zero() {
+                    return Err(ContractError::NoRewardsAvailable {});
+                }
+                shares
+            }
+        };
+        if shares.is_zero() || shares > free_shares {
+            return Err(ContractError::InsufficientFunds {});
         }
 
-        // Calculate the member's share of rewards based on the current contribution
-        let total_pooled_funds = total_available_funds;
-        let member_share = if total_pooled_funds.is_zero() {
+        let principal = if rewards_only {
             Uint128::zero()
         } else {
-            let rewards_pool_key = (cooperative_name.clone(), w_token_idx as u64);
-            let rewards_pool = REWARDS_POOLS.load(deps.storage, rewards_pool_key)?;
-            (amount * rewards_pool.total_rewards) / total_pooled_funds
+            contribution.multiply_ratio(shares, member_shares)
         };
 
-        // Check if the member has sufficient rewards
-        if member_idx >= cooperative.members.len()
-            || w_token_idx >= cooperative.members[member_idx].share.len()
-        {
-            return Err(ContractError::InsufficientRewards {});
-        }
-
-        let share = &mut cooperative.members.clone()[member_idx].share[w_token_idx];
-        if share.1 < member_share {
-            return Err(ContractError::InsufficientRewards {});
-        }
-
-        // Update contribution to zero
-        cooperative.members[member_idx].contribution[contribution_idx].1 = Uint128::zero();
+        // Shares redeem the principal plus the interest earned since the deposit
+        let amount = amount.unwrap_or_else(|| shares_value(&cooperative, token_id, shares));
+        let interest = amount.saturating_sub(principal);
 
-        // Update share to zero
-        share.1 = Uint128::zero();
+        // Pay out the rewards the member's shares earned
+        let rewards = take_rewards(deps.storage, member_rewards)?;
 
-        // Update cooperative total funds
-        let fund_idx = cooperative
-            .total_funds
-            .iter()
-            .position(|(id, _)| *id == w_token_idx as u64)
-            .ok_or(ContractError::InvalidToken {})?;
+        // Queue behind earlier withdrawals, or when the funds are lent out
+        let queue = !amount_of(&cooperative.queued_withdrawals, token_id).is_zero()
+            || available_liquidity(&cooperative, token_id) < amount;
 
-        cooperative.total_funds[fund_idx].1 =
-            cooperative.total_funds[fund_idx].1.saturating_sub(amount);
+        sub_amount(&mut member.share, token_id, shares)?;
+        sub_amount(&mut member.contribution, token_id, principal)?;
+        sub_amount(&mut cooperative.total_shares, token_id, shares)?;
+        sub_amount(&mut cooperative.total_funds, token_id, amount)?;
 
-        // Update the rewards pool's distributed rewards
-        let rewards_pool_key = (cooperative_name.clone(), w_token_idx as u64);
-        let mut rewards_pool = REWARDS_POOLS.load(deps.storage, rewards_pool_key.clone())?;
-        rewards_pool.distributed_rewards += member_share;
-        REWARDS_POOLS.save(deps.storage, rewards_pool_key, &rewards_pool)?;
+        let mut state = STATE.load(deps.storage)?;
+        sub_amount(&mut state.total_pooled_funds, token_id, amount)?;
 
-        // Handle token transfer based on type
         let mut messages: Vec<CosmosMsg> = vec![];
-        if w_token.is_native {
-            // For native tokens, create bank transfer message
-            messages.push(CosmosMsg::Bank(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: vec![Coin {
-                    denom: w_token.denom.clone(),
-                    amount,
-                }],
-            }));
-        } else {
-            // For CW20 tokens
-            let cw20_addr = w_token
-                .contract_addr
-                .clone()
-                .ok_or(ContractError::InvalidToken {})?;
+        let mut withdrawal_id = None;
+        if queue {
+            let withdrawal = QueuedWithdrawal {
+                id: state.current_withdrawal_id,
+                cooperative_name: cooperative_name.clone(),
+                token_id,
+                member: info.sender.clone(),
+                amount,
+                shares,
+                principal,
+                queued_at: env.block.time.seconds(),
+            };
+            state.current_withdrawal_id += 1;
+            add_amount(&mut cooperative.queued_withdrawals, token_id, amount);
+            WITHDRAWAL_QUEUE.save(
+                deps.storage,
+                (cooperative_name.clone(), token_id, withdrawal.id),
+                &withdrawal,
+            )?;
+            withdrawal_id = Some(withdrawal.id);
 
-            // Create CW20 transfer message
-            messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-                contract_addr: cw20_addr.to_string(),
-                msg: to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
-                    recipient: info.sender.to_string(),
-                    amount,
-                })?,
-                funds: vec![],
-            }));
+            // Rewards are not lent out and are paid right away
+            if !rewards.is_zero() {
+                messages.push(Asset::new(token_info.clone(), rewards).transfer_out(&info.sender)?);
+            }
+        } else {
+            let payout = amount + rewards;
+            messages.push(Asset::new(token_info.clone(), payout).transfer_out(&info.sender)?);
         }
 
-        // Save updated cooperative data once
-        COOPERATIVES.save(deps.storage, cooperative_name, &cooperative)?;
+        // Save updated cooperative and member data
+        COOPERATIVE_MEMBERS.save(deps.storage, member_key, &member)?;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+        STATE.save(deps.storage, &state)?;
+
+        // Pays what the pool holds towards the queue, this withdrawal included
+        let (fill_msgs, filled) =
+            fill_withdrawal_queue(deps.storage, &cooperative_name, &token_info)?;
 
         Ok(Response::new()
             .add_messages(messages)
+            .add_messages(fill_msgs)
             .add_attribute("action", "withdraw_contribution_and_rewards")
             .add_attribute("member", info.sender.to_string())
-            .add_attribute("token", token.to_string())
+            .add_attribute("token", token)
             .add_attribute("amount", amount.to_string())
-            .add_attribute("rewards", member_share.to_string()))
+            .add_attribute("shares", shares.to_string())
+            .add_attribute("interest", interest.to_string())
+            .add_attribute("rewards", rewards.to_string())
+            .add_attributes(withdrawal_id.map(|id| ("withdrawal_id", id.to_string())))
+            .add_attribute("queue_filled", filled.to_string()))
     }
 
-    pub fn execute_propose(
+    // Pays the rewards a member's pool shares earned, keeping the shares
+    pub fn execute_claim_rewards(
         deps: DepsMut,
-        _env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        proposal: Proposal,
+        token: String,
     ) -> Result<Response, ContractError> {
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Withdraw)?;
+        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let (_, token_info) = resolve_whitelisted(&cooperative, &token)?;
+        let token_id = token_info.token_id(deps.storage)?;
 
-        // Check if member exists
-        if !cooperative.members.iter().any(|m| m.address == info.sender) {
-            return Err(ContractError::Unauthorized {});
-        }
+        let member_key = (cooperative_name.clone(), info.sender.clone());
+        let mut member = COOPERATIVE_MEMBERS
+            .may_load(deps.storage, member_key.clone())?
+            .ok_or(ContractError::MemberNotFound {})?;
 
-        let mut state = STATE.load(deps.storage)?;
-        let proposal_id = state.current_proposal_id + 1;
+        let contribution = amount_of(&member.contribution, token_id);
+        let member_rewards = settle_rewards(
+            deps.storage,
+            &cooperative_name,
+            &mut cooperative,
+            &mut member,
+            token_id,
+        )?;
 
-        // Ensure required data was supplied
-        if proposal.proposal_type == ProposalType::AddMember {
-            assert!(proposal.data.new_member_addr.is_some());
-        } else if proposal.proposal_type == ProposalType::WhitelistToken {
-            assert!(proposal.data.denom.is_some());
-            match proposal.data.is_native {
-                Some(is_native) => {
-                    if !is_native {
-                        assert!(proposal.data.token_addr.is_some());
-                    }
-                }
-                None => {
-                    assert!(proposal.data.token_addr.is_none());
-                }
-            }
+        // Rewards earned while auto-compounding are reinvested rather than paid
+        let compounded = amount_of(&member.contribution, token_id) - contribution;
+        let rewards = take_rewards(deps.storage, member_rewards)?;
+        if rewards.is_zero() && compounded.is_zero() {
+            return Err(ContractError::NoRewardsAvailable {});
         }
 
-        // Construct proposal with default values
-        let proposal = Proposal {
-            id: proposal_id,
-            description: proposal.description,
-            data: proposal.data,
-            votes: vec![],
-            aye_count: 0,
-            nay_count: 0,
-            aye_weights: 0,
-            nay_weights: 0,
-            end_time: proposal.end_time,
-            quorum: proposal.quorum,
-            proposal_type: proposal.proposal_type,
-            outcome: None,
-            executed: false,
-        };
-
-        // Update storage
-        state.current_proposal_id = proposal_id;
-        STATE.save(deps.storage, &state)?;
-
-        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+        let mut messages: Vec<CosmosMsg> = vec![];
+        if !rewards.is_zero() {
+            messages.push(Asset::new(token_info, rewards).transfer_out(&info.sender)?);
+        }
 
-        let mut coop_proposals = COOPERATIVES_PROPOSALS
-            .load(deps.storage, cooperative_name.clone())
-            .unwrap_or_default();
-        coop_proposals.push(proposal_id);
-        COOPERATIVES_PROPOSALS.save(deps.storage, cooperative_name, &coop_proposals)?;
+        COOPERATIVE_MEMBERS.save(deps.storage, member_key, &member)?;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
 
         Ok(Response::new()
-            .add_attribute("action", "propose")
-            .add_attribute("proposal_id", proposal_id.to_string()))
+            .add_messages(messages)
+            .add_attribute("action", "claim_rewards")
+            .add_attribute("cooperative", cooperative_name)
+            .add_attribute("member", info.sender.to_string())
+            .add_attribute("token", token)
+            .add_attribute("rewards", rewards.to_string())
+            .add_attribute("compounded", compounded.to_string()))
     }
 
-    pub fn execute_vote(
+    // Sets whether the member's rewards are reinvested. Rewards earned so far are settled under
+    // the previous setting, and pending ones stay claimable.
+    pub fn execute_set_auto_compound(
         deps: DepsMut,
-        env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        proposal_id: u64,
-        weight: Uint128,
-        aye: bool,
+        enabled: bool,
     ) -> Result<Response, ContractError> {
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        ensure_not_frozen(deps.storage, &cooperative_name)?;
+        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
 
-        // Check if proposal has ended
-        if proposal.outcome.is_some() {
-            return Err(ContractError::ProposalEnded {});
-        }
+        let member_key = (cooperative_name.clone(), info.sender.clone());
+        let mut member = COOPERATIVE_MEMBERS
+            .may_load(deps.storage, member_key.clone())?
+            .ok_or(ContractError::MemberNotFound {})?;
 
-        // Check if member has already voted
-        if proposal.votes.iter().any(|v| v.voter == info.sender) {
-            return Err(ContractError::AlreadyVoted {});
+        let token_ids = member
+            .share
+            .iter()
+            .map(|(token_id, _)| *token_id)
+            .collect::<Vec<_>>();
+        for token_id in token_ids {
+            settle_rewards(
+                deps.storage,
+                &cooperative_name,
+                &mut cooperative,
+                &mut member,
+                token_id,
+            )?;
         }
 
-        // Record vote
-        let vote = Vote {
-            voter: info.sender.clone(),
-            conviction: weight,
-            voted_at: env.block.time.seconds(),
+        member.auto_compound = enabled;
+        COOPERATIVE_MEMBERS.save(deps.storage, member_key, &member)?;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "set_auto_compound")
+            .add_attribute("cooperative", cooperative_name)
+            .add_attribute("member", info.sender.to_string())
+            .add_attribute("enabled", enabled.to_string()))
+    }
+
+    // Spreads new rewards over the pool shares and credits the member with what their shares
+    // earned since the last settlement, reinvesting it for auto-compounding members. Runs before the member's
+    // shares change, the caller saves the member and cooperative.
+    fn settle_rewards(
+        storage: &mut dyn Storage,
+        cooperative_name: &str,
+        cooperative: &mut Cooperative,
+        member: &mut Member,
+        token_id: WhitelistedTokenId,
+    ) -> StdResult<MemberRewards> {
+        let mut member_rewards =
+            load_member_rewards(storage, cooperative_name, &member.address, token_id)?;
+
+        let pool_key = (cooperative_name.to_string(), token_id);
+        let Some(mut pool) = REWARDS_POOLS.may_load(storage, pool_key.clone())? else {
+            return Ok(member_rewards);
         };
-        proposal.votes.push(vote);
+        accrue_rewards(&mut pool, amount_of(&cooperative.total_shares, token_id))?;
+
+        let earned = earned_rewards(&pool, &member_rewards, amount_of(&member.share, token_id))?;
+        let rewards = earned - member_rewards.pending_rewards;
+        member_rewards.reward_per_share = pool.reward_per_share;
+        member_rewards.pending_rewards = earned;
+
+        // Rewards are paid in the pool's token, so new ones can be deposited as is
+        let shares = shares_for(cooperative, token_id, rewards);
+        if member.auto_compound && !shares.is_zero() {
+            add_amount(&mut member.contribution, token_id, rewards);
+            add_amount(&mut member.share, token_id, shares);
+            add_amount(&mut cooperative.total_funds, token_id, rewards);
+            add_amount(&mut cooperative.total_shares, token_id, shares);
+
+            let mut state = STATE.load(storage)?;
+            add_amount(&mut state.total_pooled_funds, token_id, rewards);
+            STATE.save(storage, &state)?;
+
+            pool.distributed_rewards += rewards;
+            member_rewards.pending_rewards -= rewards;
+        }
+        REWARDS_POOLS.save(storage, pool_key, &pool)?;
+        MEMBER_REWARDS.save(
+            storage,
+            (
+                cooperative_name.to_string(),
+                member.address.clone(),
+                token_id,
+            ),
+            &member_rewards,
+        )?;
 
-        if aye {
-            proposal.aye_count += 1;
-            proposal.aye_weights += weight.u128() as u64;
-        } else {
-            proposal.nay_count += 1;
-            proposal.nay_weights += weight.u128() as u64;
+        Ok(member_rewards)
+    }
+
+    // Marks the settled rewards of a member as paid, the caller transfers them
+    fn take_rewards(
+        storage: &mut dyn Storage,
+        mut member_rewards: MemberRewards,
+    ) -> StdResult<Uint128> {
+        let rewards = member_rewards.pending_rewards;
+        if rewards.is_zero() {
+            return Ok(rewards);
         }
 
-        // Check if proposal can be finalized
-        if let Some(quorum) = proposal.quorum {
-            let total_votes = proposal.aye_weights + proposal.nay_weights;
-            if Decimal::from_ratio(total_votes, 100u64) >= quorum {
-                proposal.outcome = Some(if proposal.aye_weights > proposal.nay_weights {
-                    ProposalOutcome::Passed
-                } else {
-                    ProposalOutcome::Rejected
-                });
+        let pool_key = (
+            member_rewards.cooperative_name.clone(),
+            member_rewards.token_id,
+        );
+        let mut pool = REWARDS_POOLS.load(storage, pool_key.clone())?;
+        pool.distributed_rewards += rewards;
+        REWARDS_POOLS.save(storage, pool_key, &pool)?;
+
+        member_rewards.pending_rewards = Uint128::zero();
+        member_rewards.last_withdrawn_rewards = rewards;
+        MEMBER_REWARDS.save(
+            storage,
+            (
+                member_rewards.cooperative_name.clone(),
+                member_rewards.member_address.clone(),
+                member_rewards.token_id,
+            ),
+            &member_rewards,
+        )?;
+
+        Ok(rewards)
+    }
+
+    // Pays the queued withdrawals of a pool in order with the liquidity it holds. The first one
+    // that can't be paid in full is filled partially and stops the run.
+    fn fill_withdrawal_queue(
+        storage: &mut dyn Storage,
+        cooperative_name: &str,
+        token_info: &AssetInfo,
+    ) -> Result<(Vec<CosmosMsg>, Uint128), ContractError> {
+        let token_id = token_info.token_id(storage)?;
+        let mut cooperative = COOPERATIVES.load(storage, cooperative_name.to_string())?;
+        let queued = amount_of(&cooperative.queued_withdrawals, token_id);
+        if queued.is_zero() {
+            return Ok((vec![], Uint128::zero()));
+        }
+
+        // Funds held for the pool, the queue is paid before anything can be borrowed
+        let mut liquidity = (amount_of(&cooperative.total_funds, token_id) + queued)
+            .saturating_sub(amount_of(&cooperative.total_borrowed, token_id));
+
+        let withdrawals = WITHDRAWAL_QUEUE
+            .prefix((cooperative_name.to_string(), token_id))
+            .range(storage, None, None, Order::Ascending)
+            .take(MAX_QUEUE_FILLS)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut messages: Vec<CosmosMsg> = vec![];
+        let mut filled = Uint128::zero();
+        for (id, mut withdrawal) in withdrawals {
+            let payment = withdrawal.amount.min(liquidity);
+            if payment.is_zero() {
+                break;
             }
+            liquidity -= payment;
+            filled += payment;
+
+            // Shares and principal left to restore on cancel follow the unpaid amount
+            withdrawal.shares -= withdrawal.shares.multiply_ratio(payment, withdrawal.amount);
+            withdrawal.principal -= withdrawal
+                .principal
+                .multiply_ratio(payment, withdrawal.amount);
+            withdrawal.amount -= payment;
+            let key = (cooperative_name.to_string(), token_id, id);
+            if withdrawal.amount.is_zero() {
+                WITHDRAWAL_QUEUE.remove(storage, key);
+            } else {
+                WITHDRAWAL_QUEUE.save(storage, key, &withdrawal)?;
+            }
+
+            messages
+                .push(Asset::new(token_info.clone(), payment).transfer_out(&withdrawal.member)?);
         }
 
-        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+        sub_amount(&mut cooperative.queued_withdrawals, token_id, filled)?;
+        COOPERATIVES.save(storage, cooperative_name.to_string(), &cooperative)?;
 
-        Ok(Response::new()
-            .add_attribute("action", "vote")
-            .add_attribute("cooperative_name", cooperative_name)
-            .add_attribute("proposal_id", proposal_id.to_string()))
+        Ok((messages, filled))
     }
 
-    pub fn execute_withdraw_weight(
+    // Takes a withdrawal out of the queue, giving the member back the shares and principal of
+    // its unpaid amount
+    pub fn execute_cancel_withdrawal(
         deps: DepsMut,
-        _env: Env,
         info: MessageInfo,
         cooperative_name: String,
-        proposal_id: u64,
+        token: String,
+        withdrawal_id: u64,
     ) -> Result<Response, ContractError> {
-        let state = STATE.load(deps.storage)?;
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Fund)?;
+        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let (_, token_info) = resolve_whitelisted(&cooperative, &token)?;
+        let token_id = token_info.token_id(deps.storage)?;
+
+        let key = (cooperative_name.clone(), token_id, withdrawal_id);
+        let withdrawal = WITHDRAWAL_QUEUE
+            .may_load(deps.storage, key.clone())?
+            .ok_or(ContractError::WithdrawalNotFound {})?;
+        if withdrawal.member != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
 
-        // Check proposal has not ended
-        if proposal.outcome.is_none() {
-            return Err(ContractError::ProposalInProcess {});
+        let member_key = (cooperative_name.clone(), info.sender.clone());
+        let mut member = COOPERATIVE_MEMBERS
+            .may_load(deps.storage, member_key.clone())?
+            .ok_or(ContractError::MemberNotFound {})?;
+
+        // Rewards are settled on the shares held before they are restored
+        settle_rewards(
+            deps.storage,
+            &cooperative_name,
+            &mut cooperative,
+            &mut member,
+            token_id,
+        )?;
+
+        WITHDRAWAL_QUEUE.remove(deps.storage, key);
+        sub_amount(
+            &mut cooperative.queued_withdrawals,
+            token_id,
+            withdrawal.amount,
+        )?;
+
+        add_amount(&mut member.share, token_id, withdrawal.shares);
+        add_amount(&mut member.contribution, token_id, withdrawal.principal);
+        add_amount(&mut cooperative.total_shares, token_id, withdrawal.shares);
+        add_amount(&mut cooperative.total_funds, token_id, withdrawal.amount);
+
+        let mut state = STATE.load(deps.storage)?;
+        add_amount(&mut state.total_pooled_funds, token_id, withdrawal.amount);
+
+        COOPERATIVE_MEMBERS.save(deps.storage, member_key, &member)?;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+        STATE.save(deps.storage, &state)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "cancel_withdrawal")
+            .add_attribute("cooperative", cooperative_name)
+            .add_attribute("withdrawal_id", withdrawal_id.to_string())
+            .add_attribute("amount", withdrawal.amount.to_string())
+            .add_attribute("shares", withdrawal.shares.to_string()))
+    }
+
+    pub fn execute_propose(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        cooperative_name: String,
+        proposal: ProposalMsg,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        if cooperative.frozen {
+            return Err(ContractError::CooperativeFrozen {});
+        }
+        ensure_not_all_paused(deps.storage, Some(&cooperative_name))?;
+
+        // Check if member exists
+        if !COOPERATIVE_MEMBERS.has(
+            deps.storage,
+            (cooperative_name.clone(), info.sender.clone()),
+        ) {
+            return Err(ContractError::Unauthorized {});
         }
 
-        // Get user weight
-        let weight = proposal.votes.iter().find(|x| x.voter == info.sender);
+        let mut state = STATE.load(deps.storage)?;
+        let proposal_id = state.current_proposal_id + 1;
 
-        if weight.is_none() {
-            return Err(ContractError::NoWeightsToWithdraw {});
+        // Ensure required data was supplied
+        if proposal.proposal_type == ProposalType::AddMember {
+            assert!(proposal.data.new_member_addr.is_some());
+        } else if proposal.proposal_type == ProposalType::CancelProposal {
+            assert!(proposal.data.proposal_id.is_some());
+        } else if proposal.proposal_type == ProposalType::WhitelistToken {
+            validate_whitelisted_token(deps.as_ref(), &proposed_token(&proposal.data)?)?;
+        }
+
+        // Escrow proposal deposit
+        let mut messages: Vec<CosmosMsg> = vec![];
+        if let Some(deposit) = &cooperative.proposal_deposit {
+            messages.extend(Asset::from(deposit).transfer_in(&deps.querier, &env, &info)?);
         }
 
-        let weight = weight.unwrap().conviction;
+        // Fall back to the default voting period and quorum when not set
+        let end_time = if proposal.end_time == 0 {
+            env.block.time.seconds() + state.governance.voting_period
+        } else {
+            proposal.end_time
+        };
+        let quorum = proposal.quorum.or(Some(state.governance.quorum));
 
-        let msg: Vec<CosmosMsg> = vec![CosmosMsg::Bank(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: vec![Coin {
-                denom: state.weight_token,
-                amount: weight,
-            }],
-        })];
+        // Construct proposal with default values
+        let proposal = Proposal {
+            id: proposal_id,
+            cooperative: cooperative_name.clone(),
+            proposer: info.sender.clone(),
+            description: proposal.description,
+            data: proposal.data,
+            aye_count: 0,
+            nay_count: 0,
+            aye_weights: Uint128::zero(),
+            nay_weights: Uint128::zero(),
+            end_time,
+            quorum,
+            proposal_type: proposal.proposal_type,
+            outcome: None,
+            executed: false,
+            cancelled: false,
+            deposit: cooperative.proposal_deposit.clone(),
+            deposit_settled: false,
+        };
 
-        // Update weight to prevent double spending
-        let idx = proposal.votes.iter().position(|x| x.voter == info.sender);
-        proposal.votes[idx.unwrap()].conviction = 0u128.into();
+        // Update storage
+        state.current_proposal_id = proposal_id;
+        STATE.save(deps.storage, &state)?;
 
         PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
 
         Ok(Response::new()
-            .add_messages(msg)
-            .add_attribute("action", "withdraw weight")
-            .add_attribute("cooperative_name", cooperative_name)
+            .add_messages(messages)
+            .add_attribute("action", "propose")
             .add_attribute("proposal_id", proposal_id.to_string()))
     }
 
-    pub fn execute_proposal(
+    #[allow(clippy::too_many_arguments)]
+    pub fn execute_vote(
         deps: DepsMut,
         env: Env,
         info: MessageInfo,
         cooperative_name: String,
         proposal_id: u64,
+        weight: Uint128,
+        aye: bool,
+        conviction: Conviction,
     ) -> Result<Response, ContractError> {
+        let state = STATE.load(deps.storage)?;
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
-        let proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Vote)?;
+        let mut proposal = load_cooperative_proposal(deps.storage, &cooperative_name, proposal_id)?;
 
-        // Ensure proposal has not been executed
-        if proposal.executed {
-            return Err(ContractError::ProposalAlreadyExecuted {});
+        // Check if voter is member
+        if !COOPERATIVE_MEMBERS.has(
+            deps.storage,
+            (cooperative_name.clone(), info.sender.clone()),
+        ) {
+            return Err(ContractError::Unauthorized {});
         }
 
-        // Check if signer is member
-        if !cooperative
-            .members
-            .iter()
-            .any(|m| m.address == info.sender.clone())
-        {
-            return Err(ContractError::Unauthorized {});
+        // Check if proposal was cancelled
+        if proposal.cancelled {
+            return Err(ContractError::ProposalCancelled {});
         }
 
-        // Check proposal has ended
-        if env.block.time.seconds() >= proposal.end_time {
-            return Err(ContractError::ProposalInProcess {});
+        // Check if proposal has ended
+        if proposal.outcome.is_some() || env.block.time.seconds() >= proposal.end_time {
+            return Err(ContractError::ProposalEnded {});
         }
 
-        // Check proposal has passed
-        match proposal.outcome {
-            Some(outcome) => {
-                if outcome == ProposalOutcome::Rejected {
-                    return Err(ContractError::ProposalRejected {});
+        // Check if member has already voted
+        if VOTES.has(deps.storage, (proposal_id, info.sender.clone())) {
+            return Err(ContractError::AlreadyVoted {});
+        }
+
+        // Ensure weight is escrowed in weight token
+        if weight.is_zero() {
+            return Err(ContractError::FundsMustMatchAmount {});
+        }
+        weight_asset(&state, weight).transfer_in(&deps.querier, &env, &info)?;
+
+        // Apply conviction multiplier and lock
+        let effective_weight = weight.mul_floor(conviction.multiplier());
+        let locked_until =
+            proposal.end_time + conviction.lock_periods() * state.governance.vote_lock_period;
+
+        // Record vote
+        let vote = Vote {
+            proposal_id,
+            voter: info.sender.clone(),
+            aye: Some(aye),
+            weight,
+            effective_weight,
+            conviction,
+            voted_at: env.block.time.seconds(),
+            locked_until,
+            withdrawn: false,
+        };
+        VOTES.save(deps.storage, (proposal_id, info.sender.clone()), &vote)?;
+
+        if aye {
+            proposal.aye_count += 1;
+            proposal.aye_weights = proposal
+                .aye_weights
+                .checked_add(effective_weight)
+                .map_err(cosmwasm_std::StdError::from)?;
+        } else {
+            proposal.nay_count += 1;
+            proposal.nay_weights = proposal
+                .nay_weights
+                .checked_add(effective_weight)
+                .map_err(cosmwasm_std::StdError::from)?;
+        }
+
+        // Check if proposal can be finalized
+        let mut messages: Vec<CosmosMsg> = vec![];
+        if let Some(quorum) = proposal.quorum {
+            let total_votes = proposal
+                .aye_weights
+                .checked_add(proposal.nay_weights)
+                .map_err(cosmwasm_std::StdError::from)?;
+            if Decimal::from_ratio(total_votes, 100u128) >= quorum {
+                proposal.outcome = Some(if proposal.aye_weights > proposal.nay_weights {
+                    ProposalOutcome::Passed
                 } else {
-                    let proposal_type = proposal.proposal_type;
-                    let proposal_data = proposal.data;
-                    match proposal_type {
-                        ProposalType::WhitelistToken => {
-                            let res = execute_add_whitelisted_token(
-                                deps,
-                                info.clone(),
-                                proposal_id,
-                                cooperative_name,
-                                proposal_data.denom.unwrap(),
-                                Some(proposal_data.token_addr.unwrap()),
-                                proposal_data.is_native.unwrap(),
-                                proposal_data.max_loan_ratio.unwrap(),
-                            )?;
-                            return Ok(res);
-                        }
-                        ProposalType::AddMember => {
-                            let res = execute_add_member(
-                                deps,
-                                env,
-                                info.clone(),
-                                proposal_id,
-                                cooperative_name,
-                                proposal_data.new_member_addr.unwrap(),
-                            )?;
-                            return Ok(res);
-                        }
-                        _ => {
-                            return Err(ContractError::NotImplemented {});
-                        }
+                    ProposalOutcome::Rejected
+                });
+
+                // Refund proposal deposit once quorum is reached
+                if let Some(deposit) = &proposal.deposit {
+                    if !proposal.deposit_settled {
+                        messages.push(Asset::from(deposit).transfer_out(&proposal.proposer)?);
+                        proposal.deposit_settled = true;
                     }
                 }
             }
-            None => {
-                return Err(ContractError::ProposalInProcess {});
-            }
         }
+
+        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "vote")
+            .add_attribute("cooperative_name", cooperative_name)
+            .add_attribute("proposal_id", proposal_id.to_string())
+            .add_attribute("effective_weight", effective_weight.to_string())
+            .add_attribute("locked_until", locked_until.to_string()))
     }
 
-    fn execute_add_whitelisted_token(
+    pub fn execute_withdraw_weight(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
-        proposal_id: u64,
         cooperative_name: String,
-        denom: String,
-        contract_addr: Option<Addr>,
-        is_native: bool,
-        max_loan_ratio: Decimal,
+        proposal_id: u64,
     ) -> Result<Response, ContractError> {
-        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
-        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
-        let mut state = STATE.load(deps.storage)?;
-        let whitelisted_token_id = state.current_whitelisted_token_id + 1;
-
-        // Verify caller is contract owner
-        if info.sender != state.owner {
-            return Err(ContractError::Unauthorized {});
-        }
+        let state = STATE.load(deps.storage)?;
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_paused(deps.storage, &cooperative_name, PausableOperation::Withdraw)?;
+        let proposal = load_cooperative_proposal(deps.storage, &cooperative_name, proposal_id)?;
+        let now = env.block.time.seconds();
 
-        // Check not already whitelisted
-        if cooperative
-            .whitelisted_tokens
-            .iter()
-            .any(|m| m.denom == denom)
-        {
-            return Err(ContractError::TokenAlreadyWhitelisted {});
+        // Check proposal has ended
+        if proposal.outcome.is_none() && now < proposal.end_time {
+            return Err(ContractError::ProposalInProcess {});
         }
 
-        // Ensure whitelisted tokens is less than or equal to 5.
-        assert!(
-            cooperative.whitelisted_tokens.len() <= 5,
-            "{}",
-            ContractError::MaxWhitelistedTokensReached {}
-        );
+        // Get user vote
+        let mut vote = VOTES
+            .may_load(deps.storage, (proposal_id, info.sender.clone()))?
+            .ok_or(ContractError::NoWeightsToWithdraw {})?;
 
-        // Create new whitelisted token
-        let token = WhitelistedToken {
-            denom,
-            contract_addr,
-            is_native,
-            max_loan_ratio,
-        };
+        if vote.withdrawn || vote.weight.is_zero() {
+            return Err(ContractError::NoWeightsToWithdraw {});
+        }
 
-        // Update storage
-        state.current_whitelisted_token_id = whitelisted_token_id;
-        STATE.save(deps.storage, &state)?;
+        // Check conviction lock has expired
+        if now < vote.locked_until {
+            return Err(ContractError::VoteLocked {
+                unlock_at: vote.locked_until,
+            });
+        }
 
-        cooperative.whitelisted_tokens.push(token.clone());
-        COOPERATIVES.save(
-            deps.storage,
-            cooperative_name.trim().to_lowercase(),
-            &cooperative,
-        )?;
+        let transfer_msg = weight_asset(&state, vote.weight).transfer_out(&info.sender)?;
 
-        proposal.executed = true;
-        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+        // Mark released to prevent double spending, keeping the receipt's weight
+        vote.withdrawn = true;
+        VOTES.save(deps.storage, (proposal_id, info.sender.clone()), &vote)?;
 
         Ok(Response::new()
-            .add_attribute("action", "add_whitelisted_token")
-            .add_attribute("token", token.denom))
+            .add_message(transfer_msg)
+            .add_attribute("action", "withdraw weight")
+            .add_attribute("cooperative_name", cooperative_name)
+            .add_attribute("proposal_id", proposal_id.to_string()))
     }
 
-    fn execute_add_member(
+    pub fn execute_settle_proposal_deposit(
         deps: DepsMut,
         env: Env,
-        info: MessageInfo,
-        proposal_id: u64,
+        _info: MessageInfo,
         cooperative_name: String,
-        new_member_addr: Addr,
+        proposal_id: u64,
     ) -> Result<Response, ContractError> {
         let cooperative_name = cooperative_name.trim().to_lowercase();
-        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
-        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
-
-        // Check if member already exists
-        if cooperative
-            .members
-            .iter()
-            .any(|m| m.address == new_member_addr)
-        {
-            return Err(ContractError::AlreadyMember {});
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        if cooperative.frozen {
+            return Err(ContractError::CooperativeFrozen {});
         }
+        let mut proposal = load_cooperative_proposal(deps.storage, &cooperative_name, proposal_id)?;
 
-        let new_member = Member {
-            address: new_member_addr.clone(),
-            contribution: vec![],
-            share: vec![],
-            joined_at: env.block.time.seconds(),
-            reputation_score: Decimal::zero(),
-            loans: vec![],
+        // Deposits of proposals reaching quorum are refunded when voting
+        let deposit = match &proposal.deposit {
+            Some(deposit) if !proposal.deposit_settled && proposal.outcome.is_none() => {
+                deposit.clone()
+            }
+            _ => return Err(ContractError::NoProposalDeposit {}),
         };
 
-        cooperative.members.push(new_member);
-        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+        // Check proposal has ended without reaching quorum
+        if env.block.time.seconds() < proposal.end_time {
+            return Err(ContractError::ProposalInProcess {});
+        }
 
-        // Update member's cooperative list
-        let mut member_coops = MEMBERS
-            .load(deps.storage, info.sender.clone())
-            .unwrap_or_default();
-        member_coops.push(cooperative_name.clone());
-        MEMBERS.save(deps.storage, new_member_addr, &member_coops)?;
+        // Slash deposit into the cooperative rewards pool
+        let deposit_info = Asset::from(&deposit).info;
+        if !cooperative
+            .whitelisted_tokens
+            .iter()
+            .any(|x| deposit_info.matches(x))
+        {
+            return Err(ContractError::InvalidProposalDeposit {});
+        }
+        let token_id = deposit_info.token_id(deps.storage)?;
+        let rewards_pool_key = (cooperative_name.clone(), token_id);
+        let mut rewards_pool = REWARDS_POOLS
+            .may_load(deps.storage, rewards_pool_key.clone())?
+            .unwrap_or(CooperativeRewardsPool {
+                cooperative_name: cooperative_name.clone(),
+                token_id,
+                total_rewards: Uint128::zero(),
+                distributed_rewards: Uint128::zero(),
+                indexed_rewards: Uint128::zero(),
+                reward_per_share: Decimal256::zero(),
+            });
+        rewards_pool.total_rewards += deposit.amount;
+        REWARDS_POOLS.save(deps.storage, rewards_pool_key, &rewards_pool)?;
 
-        // Update proposal
-        proposal.executed = true;
+        proposal.deposit_settled = true;
         PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
 
         Ok(Response::new()
-            .add_attribute("action", "join_cooperative")
-            .add_attribute("cooperative", cooperative_name))
+            .add_attribute("action", "slash_proposal_deposit")
+            .add_attribute("cooperative_name", cooperative_name)
+            .add_attribute("proposal_id", proposal_id.to_string())
+            .add_attribute("amount", deposit.amount.to_string()))
     }
-}
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetCooperative { cooperative_name } => {
-            to_json_binary(&query::get_cooperative(deps, cooperative_name)?)
+    // Loads a proposal, ensuring it belongs to the given cooperative
+    fn load_cooperative_proposal(
+        storage: &dyn Storage,
+        cooperative_name: &str,
+        proposal_id: u64,
+    ) -> Result<Proposal, ContractError> {
+        let proposal = PROPOSALS
+            .may_load(storage, proposal_id)?
+            .ok_or(ContractError::ProposalNotFound {})?;
+
+        if proposal.cooperative != cooperative_name {
+            return Err(ContractError::ProposalNotInCooperative {});
         }
-        QueryMsg::GetMemberInfo {
-            cooperative_name,
-            member,
-        } => to_json_binary(&query::get_member_info(deps, cooperative_name, member)?),
-        QueryMsg::MemberContributionAndShare {
-            cooperative_name,
-            member_address,
-        } => to_json_binary(&query::query_member_contribution_and_share(
-            deps,
+
+        Ok(proposal)
+    }
+
+    // Vote weight escrowed in the weight token
+    fn weight_asset(state: &State, amount: Uint128) -> Asset {
+        Asset::new(
+            AssetInfo::Native {
+                denom: state.weight_token.clone(),
+            },
+            amount,
+        )
+    }
+
+    pub fn execute_cancel_proposal(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        cooperative_name: String,
+        proposal_id: u64,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_frozen(deps.storage, &cooperative_name)?;
+        let proposal = load_cooperative_proposal(deps.storage, &cooperative_name, proposal_id)?;
+
+        // Only the proposer can cancel, and only before any votes
+        if proposal.proposer != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        if proposal.aye_count + proposal.nay_count > 0 {
+            return Err(ContractError::ProposalInProcess {});
+        }
+
+        ensure_not_ended(&proposal, env.block.time.seconds())?;
+
+        let messages = cancel_proposal(deps.storage, &cooperative_name, proposal_id)?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "cancel_proposal")
+            .add_attribute("cooperative_name", cooperative_name)
+            .add_attribute("proposal_id", proposal_id.to_string()))
+    }
+
+    fn execute_cancel_proposal_by_governance(
+        deps: DepsMut,
+        env: Env,
+        proposal_id: u64,
+        cooperative_name: String,
+        target_proposal_id: u64,
+    ) -> Result<Response, ContractError> {
+        // Cancelling itself would be overwritten by the executed flag below
+        if target_proposal_id == proposal_id {
+            return Err(ContractError::InvalidProposal {});
+        }
+
+        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        let target =
+            load_cooperative_proposal(deps.storage, &cooperative_name, target_proposal_id)?;
+        ensure_not_ended(&target, env.block.time.seconds())?;
+
+        let messages = cancel_proposal(deps.storage, &cooperative_name, target_proposal_id)?;
+
+        // Update proposal
+        proposal.executed = true;
+        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_messages(messages)
+            .add_attribute("action", "cancel_proposal")
+            .add_attribute("cooperative_name", cooperative_name)
+            .add_attribute("proposal_id", target_proposal_id.to_string()))
+    }
+
+    // An ended proposal keeps its deposit settlement, so it cannot be refunded on cancel
+    fn ensure_not_ended(proposal: &Proposal, now: u64) -> Result<(), ContractError> {
+        if proposal.outcome.is_some() || proposal.deposit_settled || now >= proposal.end_time {
+            return Err(ContractError::ProposalEnded {});
+        }
+        Ok(())
+    }
+
+    // Marks a proposal cancelled, refunding escrowed vote weights and deposit
+    fn cancel_proposal(
+        storage: &mut dyn Storage,
+        cooperative_name: &str,
+        proposal_id: u64,
+    ) -> Result<Vec<CosmosMsg>, ContractError> {
+        let state = STATE.load(storage)?;
+        let mut proposal = load_cooperative_proposal(storage, cooperative_name, proposal_id)?;
+
+        if proposal.executed {
+            return Err(ContractError::ProposalAlreadyExecuted {});
+        }
+
+        if proposal.cancelled {
+            return Err(ContractError::ProposalCancelled {});
+        }
+
+        let mut messages: Vec<CosmosMsg> = vec![];
+
+        // Refund escrowed vote weights
+        let votes = VOTES
+            .prefix(proposal_id)
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (voter, mut vote) in votes {
+            if vote.withdrawn || vote.weight.is_zero() {
+                continue;
+            }
+
+            messages.push(weight_asset(&state, vote.weight).transfer_out(&voter)?);
+            vote.withdrawn = true;
+            VOTES.save(storage, (proposal_id, voter), &vote)?;
+        }
+
+        // Refund proposal deposit
+        if let Some(deposit) = &proposal.deposit {
+            if !proposal.deposit_settled {
+                messages.push(Asset::from(deposit).transfer_out(&proposal.proposer)?);
+                proposal.deposit_settled = true;
+            }
+        }
+
+        // Stays listed in the cooperative with a cancelled status
+        proposal.cancelled = true;
+        PROPOSALS.save(storage, proposal_id, &proposal)?;
+
+        Ok(messages)
+    }
+
+    pub fn execute_proposal(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        cooperative_name: String,
+        proposal_id: u64,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        ensure_not_frozen(deps.storage, &cooperative_name)?;
+        ensure_not_all_paused(deps.storage, Some(&cooperative_name))?;
+        let proposal = load_cooperative_proposal(deps.storage, &cooperative_name, proposal_id)?;
+
+        // Ensure proposal has not been executed
+        if proposal.executed {
+            return Err(ContractError::ProposalAlreadyExecuted {});
+        }
+
+        // Ensure proposal has not been cancelled
+        if proposal.cancelled {
+            return Err(ContractError::ProposalCancelled {});
+        }
+
+        // Check if signer is member
+        if !COOPERATIVE_MEMBERS.has(
+            deps.storage,
+            (cooperative_name.clone(), info.sender.clone()),
+        ) {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        // Check proposal has ended
+        if env.block.time.seconds() >= proposal.end_time {
+            return Err(ContractError::ProposalInProcess {});
+        }
+
+        // Check proposal has passed
+        match proposal.outcome {
+            Some(outcome) => {
+                if outcome == ProposalOutcome::Rejected {
+                    Err(ContractError::ProposalRejected {})
+                } else {
+                    let proposal_type = proposal.proposal_type;
+                    let proposal_data = proposal.data;
+                    match proposal_type {
+                        ProposalType::WhitelistToken => {
+                            let res = execute_add_whitelisted_token(
+                                deps,
+                                info.clone(),
+                                proposal_id,
+                                cooperative_name,
+                                proposed_token(&proposal_data)?,
+                            )?;
+                            Ok(res)
+                        }
+                        ProposalType::AddMember => {
+                            let res = execute_add_member(
+                                deps,
+                                env,
+                                proposal_id,
+                                cooperative_name,
+                                proposal_data.new_member_addr.unwrap(),
+                            )?;
+                            Ok(res)
+                        }
+                        ProposalType::CancelProposal => {
+                            let res = execute_cancel_proposal_by_governance(
+                                deps,
+                                env,
+                                proposal_id,
+                                cooperative_name,
+                                proposal_data.proposal_id.unwrap(),
+                            )?;
+                            Ok(res)
+                        }
+                        _ => Err(ContractError::NotImplemented {}),
+                    }
+                }
+            }
+            None => Err(ContractError::ProposalInProcess {}),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_add_whitelisted_token(
+        deps: DepsMut,
+        info: MessageInfo,
+        proposal_id: u64,
+        cooperative_name: String,
+        token: WhitelistedToken,
+    ) -> Result<Response, ContractError> {
+        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let mut state = STATE.load(deps.storage)?;
+
+        // Verify caller is contract owner
+        if info.sender != state.owner {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        validate_whitelisted_token(deps.as_ref(), &token)?;
+
+        // Check not already whitelisted
+        let token_info = AssetInfo::from_whitelisted(&token)?;
+        if cooperative
+            .whitelisted_tokens
+            .iter()
+            .any(|x| token_info.matches(x))
+        {
+            return Err(ContractError::TokenAlreadyWhitelisted {});
+        }
+
+        // Ensure the cooperative has room for another token
+        if cooperative.whitelisted_tokens.len() as u64 >= state.max_tokens_per_cooperative {
+            return Err(ContractError::MaxWhitelistedTokensReached {});
+        }
+
+        // Update storage
+        let token_id = register_token(deps.storage, &mut state, &token)?;
+        STATE.save(deps.storage, &state)?;
+
+        cooperative.whitelisted_tokens.push(token.clone());
+        COOPERATIVES.save(
+            deps.storage,
+            cooperative_name.trim().to_lowercase(),
+            &cooperative,
+        )?;
+
+        proposal.executed = true;
+        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "add_whitelisted_token")
+            .add_attribute("token", token.denom)
+            .add_attribute("symbol", token.symbol)
+            .add_attribute("token_id", token_id.to_string()))
+    }
+
+    fn execute_add_member(
+        deps: DepsMut,
+        env: Env,
+        proposal_id: u64,
+        cooperative_name: String,
+        new_member_addr: Addr,
+    ) -> Result<Response, ContractError> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let mut proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+        let mut cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+
+        // Check if member already exists
+        let member_key = (cooperative_name.clone(), new_member_addr.clone());
+        if COOPERATIVE_MEMBERS.has(deps.storage, member_key.clone()) {
+            return Err(ContractError::AlreadyMember {});
+        }
+
+        // Ensure the cooperative has room for another member
+        let state = STATE.load(deps.storage)?;
+        if cooperative.member_count >= state.max_members_per_cooperative {
+            return Err(ContractError::MaxMembersReached {});
+        }
+
+        let new_member = Member {
+            address: new_member_addr.clone(),
+            contribution: vec![],
+            share: vec![],
+            joined_at: env.block.time.seconds(),
+            reputation_score: Decimal::zero(),
+            auto_compound: false,
+        };
+
+        COOPERATIVE_MEMBERS.save(deps.storage, member_key, &new_member)?;
+        cooperative.member_count += 1;
+        COOPERATIVES.save(deps.storage, cooperative_name.clone(), &cooperative)?;
+
+        // Update member's cooperative list
+        let mut member_coops = MEMBERS
+            .load(deps.storage, new_member_addr.clone())
+            .unwrap_or_default();
+        member_coops.push(cooperative_name.clone());
+        MEMBERS.save(deps.storage, new_member_addr, &member_coops)?;
+
+        // Update proposal
+        proposal.executed = true;
+        PROPOSALS.save(deps.storage, proposal_id, &proposal)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "join_cooperative")
+            .add_attribute("cooperative", cooperative_name))
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_json_binary(&query::get_config(deps)?),
+        QueryMsg::ProtocolFees {} => to_json_binary(&query::get_protocol_fees(deps)?),
+        QueryMsg::PauseStatus { cooperative_name } => {
+            to_json_binary(&query::get_pause_status(deps, cooperative_name)?)
+        }
+        QueryMsg::GetCooperative { cooperative_name } => {
+            to_json_binary(&query::get_cooperative(deps, cooperative_name)?)
+        }
+        QueryMsg::GetMemberInfo {
+            cooperative_name,
+            member,
+        } => to_json_binary(&query::get_member_info(deps, cooperative_name, member)?),
+        QueryMsg::MemberContributionAndShare {
+            cooperative_name,
+            member_address,
+        } => to_json_binary(&query::query_member_contribution_and_share(
+            deps,
             cooperative_name,
             member_address.to_string(),
         )?),
         QueryMsg::GetProposal { proposal_id } => {
             to_json_binary(&query::get_proposal(deps, proposal_id)?)
         }
+        QueryMsg::ListProposals {
+            cooperative_name,
+            status,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_proposals(
+            deps,
+            env,
+            cooperative_name,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetVote { proposal_id, voter } => {
+            to_json_binary(&query::get_vote(deps, proposal_id, voter)?)
+        }
+        QueryMsg::ListVotesByVoter {
+            voter,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_votes_by_voter(
+            deps,
+            voter,
+            start_after,
+            limit,
+        )?),
         QueryMsg::GetWhitelistedTokens { cooperative_name } => {
             to_json_binary(&query::get_whitelisted_tokens(deps, cooperative_name)?)
         }
-        QueryMsg::ListCooperatives {} => to_json_binary(&query::list_cooperative(deps)?),
-        QueryMsg::GetTokenId { token } => to_json_binary(&query::get_token_id(deps, token)?),
+        QueryMsg::ListMembers {
+            cooperative_name,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_members(
+            deps,
+            cooperative_name,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetLoan { loan_id } => to_json_binary(&query::get_loan(deps, loan_id)?),
+        QueryMsg::ListLoans {
+            cooperative_name,
+            status,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_loans(
+            deps,
+            cooperative_name,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::ListLoansByBorrower {
+            borrower,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_loans_by_borrower(
+            deps,
+            borrower,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::ListLoansByToken {
+            token,
+            status,
+            start_after,
+            limit,
+        } => to_json_binary(&query::list_loans_by_token(
+            deps,
+            token,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::ListCooperatives {} => to_json_binary(&query::list_cooperative(deps)?),
+        QueryMsg::GetTokenId { token } => to_json_binary(&query::get_token_id(deps, token)?),
+        QueryMsg::ListTokens { start_after, limit } => {
+            to_json_binary(&query::list_tokens(deps, start_after, limit)?)
+        }
+        QueryMsg::Pool {
+            cooperative_name,
+            token,
+        } => to_json_binary(&query::get_pool(deps, cooperative_name, token)?),
+        QueryMsg::WithdrawalQueue {
+            cooperative_name,
+            token,
+            start_after,
+            limit,
+        } => to_json_binary(&query::withdrawal_queue(
+            deps,
+            cooperative_name,
+            token,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::PendingRewards {
+            cooperative_name,
+            member,
+            token,
+        } => to_json_binary(&query::pending_rewards(
+            deps,
+            cooperative_name,
+            member,
+            token,
+        )?),
+        QueryMsg::WithdrawalPosition {
+            cooperative_name,
+            token,
+            withdrawal_id,
+        } => to_json_binary(&query::withdrawal_position(
+            deps,
+            cooperative_name,
+            token,
+            withdrawal_id,
+        )?),
+    }
+}
+
+pub mod query {
+    use cosmwasm_std::StdError;
+    use cw_storage_plus::Bound;
+
+    use crate::{
+        asset::AssetInfo,
+        msg::{
+            GetConfigResponse, GetCooperativeResponse, GetListCooperativesResponse,
+            GetListLoansResponse, GetListMembersResponse, GetListProposalsResponse,
+            GetListTokensResponse, GetListVotesResponse, GetLoanResponse, GetMemberInfoResponse,
+            GetPauseStatusResponse, GetPendingRewardsResponse, GetPoolResponse,
+            GetProposalResponse, GetProtocolFeesResponse, GetTokenIdResponse, GetVoteResponse,
+            GetWhitelistedTokensResponse, GetWithdrawalPositionResponse,
+            GetWithdrawalQueueResponse, MemberContributionAndShareResponse, ProposalSummary,
+            TokenAmount, TokenInfo,
+        },
+        state::{
+            CorporativeName, ProposalId, ProposalStatus, COOPERATIVE_PAUSED, PAUSED, PENDING_OWNER,
+            SUDO_COOPERATIVE_PAUSED, SUDO_PAUSED,
+        },
+    };
+
+    // pagination limits for list queries
+    const DEFAULT_LIMIT: u32 = 10;
+    const MAX_LIMIT: u32 = 30;
+
+    use super::*;
+
+    pub fn get_config(deps: Deps) -> StdResult<GetConfigResponse> {
+        let state = STATE.load(deps.storage)?;
+        let pending_owner = PENDING_OWNER.may_load(deps.storage)?;
+
+        Ok(GetConfigResponse {
+            owner: state.owner,
+            guardian: state.guardian,
+            weight_token: state.weight_token,
+            protocol_fee: state.protocol_fee,
+            max_members_per_cooperative: state.max_members_per_cooperative,
+            max_tokens_per_cooperative: state.max_tokens_per_cooperative,
+            governance: state.governance,
+            pending_owner,
+        })
+    }
+
+    pub fn get_protocol_fees(deps: Deps) -> StdResult<GetProtocolFeesResponse> {
+        let state = STATE.load(deps.storage)?;
+        let fees = PROTOCOL_TREASURY
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(GetProtocolFeesResponse {
+            protocol_fee: state.protocol_fee,
+            fees,
+        })
+    }
+
+    pub fn get_pause_status(
+        deps: Deps,
+        cooperative_name: Option<String>,
+    ) -> StdResult<GetPauseStatusResponse> {
+        let (cooperative, sudo_cooperative) = match cooperative_name {
+            Some(name) => {
+                let name = name.trim().to_lowercase();
+                (
+                    COOPERATIVE_PAUSED
+                        .may_load(deps.storage, name.clone())?
+                        .unwrap_or_default(),
+                    SUDO_COOPERATIVE_PAUSED
+                        .may_load(deps.storage, name)?
+                        .unwrap_or_default(),
+                )
+            }
+            None => (vec![], vec![]),
+        };
+
+        Ok(GetPauseStatusResponse {
+            global: PAUSED.may_load(deps.storage)?.unwrap_or_default(),
+            cooperative,
+            sudo_global: SUDO_PAUSED.may_load(deps.storage)?.unwrap_or_default(),
+            sudo_cooperative,
+        })
+    }
+
+    pub fn get_cooperative(
+        deps: Deps,
+        cooperative_name: String,
+    ) -> StdResult<GetCooperativeResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let corporative = COOPERATIVES.load(deps.storage, cooperative_name)?;
+
+        Ok(GetCooperativeResponse { corporative })
+    }
+
+    pub fn get_member_info(
+        deps: Deps,
+        cooperative_name: String,
+        member: Addr,
+    ) -> StdResult<GetMemberInfoResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let member_data = COOPERATIVE_MEMBERS
+            .may_load(deps.storage, (cooperative_name.clone(), member.clone()))?;
+
+        if member_data.is_none() {
+            return Ok(GetMemberInfoResponse {
+                info: Member {
+                    address: Addr::unchecked("0"),
+                    contribution: vec![],
+                    share: vec![],
+                    joined_at: 0,
+                    reputation_score: Decimal::zero(),
+                    auto_compound: false,
+                },
+                loans: vec![],
+            });
+        }
+
+        let info = member_data.unwrap();
+        let loans = member_loans(deps, cooperative_name, member)?;
+        Ok(GetMemberInfoResponse { info, loans })
+    }
+
+    pub fn list_members(
+        deps: Deps,
+        cooperative_name: String,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListMembersResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let members = COOPERATIVE_MEMBERS
+            .prefix(cooperative_name)
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, member)| member))
+            .collect::<StdResult<Vec<Member>>>()?;
+
+        Ok(GetListMembersResponse { members })
+    }
+
+    fn member_loans(deps: Deps, cooperative_name: String, member: Addr) -> StdResult<Vec<Loan>> {
+        let loans = LOANS
+            .idx
+            .borrower
+            .prefix(member)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, loan)| loan))
+            .collect::<StdResult<Vec<Loan>>>()?;
+
+        Ok(loans
+            .into_iter()
+            .filter(|loan| loan.cooperative == cooperative_name)
+            .collect())
+    }
+
+    pub fn get_loan(deps: Deps, loan_id: u64) -> StdResult<GetLoanResponse> {
+        let loan = LOANS.load(deps.storage, loan_id)?;
+
+        Ok(GetLoanResponse { loan })
+    }
+
+    pub fn list_loans(
+        deps: Deps,
+        cooperative_name: Option<CorporativeName>,
+        status: Option<LoanStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListLoansResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let loans = match (cooperative_name, status) {
+            (Some(cooperative_name), Some(status)) => LOANS
+                .idx
+                .cooperative_status
+                .prefix((cooperative_name.trim().to_lowercase(), status.to_string()))
+                .range(deps.storage, min, None, Order::Ascending),
+            (Some(cooperative_name), None) => LOANS
+                .idx
+                .cooperative
+                .prefix(cooperative_name.trim().to_lowercase())
+                .range(deps.storage, min, None, Order::Ascending),
+            (None, Some(status)) => LOANS.idx.status.prefix(status.to_string()).range(
+                deps.storage,
+                min,
+                None,
+                Order::Ascending,
+            ),
+            (None, None) => LOANS.range(deps.storage, min, None, Order::Ascending),
+        }
+        .map(|item| item.map(|(_, loan)| loan))
+        .take(limit)
+        .collect::<StdResult<Vec<Loan>>>()?;
+
+        Ok(GetListLoansResponse { loans })
+    }
+
+    pub fn list_loans_by_borrower(
+        deps: Deps,
+        borrower: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListLoansResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let loans = LOANS
+            .idx
+            .borrower
+            .prefix(borrower)
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, loan)| loan))
+            .collect::<StdResult<Vec<Loan>>>()?;
+
+        Ok(GetListLoansResponse { loans })
+    }
+
+    pub fn list_loans_by_token(
+        deps: Deps,
+        token: String,
+        status: Option<LoanStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListLoansResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let loans = LOANS
+            .idx
+            .token
+            .prefix(token)
+            .range(deps.storage, min, None, Order::Ascending)
+            .map(|item| item.map(|(_, loan)| loan))
+            .filter(|item| {
+                item.as_ref().map_or(true, |loan| {
+                    status.as_ref().map_or(true, |s| loan.status == *s)
+                })
+            })
+            .take(limit)
+            .collect::<StdResult<Vec<Loan>>>()?;
+
+        Ok(GetListLoansResponse { loans })
+    }
+
+    pub fn query_member_contribution_and_share(
+        deps: Deps,
+        cooperative_name: String,
+        member_address: String,
+    ) -> StdResult<MemberContributionAndShareResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let member_addr = deps.api.addr_validate(&member_address)?;
+
+        // Get the cooperative
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+
+        // Find the member in the cooperative
+        let member = COOPERATIVE_MEMBERS
+            .may_load(
+                deps.storage,
+                (cooperative_name.clone(), member_addr.clone()),
+            )?
+            .ok_or_else(|| {
+                cosmwasm_std::StdError::generic_err(format!(
+                    "Member {} not found in cooperative {}",
+                    member_address, cooperative_name
+                ))
+            })?;
+
+        // Collect token information for all tokens in the cooperative
+        let mut token_info: Vec<TokenInfo> = Vec::new();
+        for token in &cooperative.whitelisted_tokens {
+            let token_key = AssetInfo::from_whitelisted(token)
+                .map_err(|e| StdError::generic_err(e.to_string()))?
+                .to_string();
+            let token_id = TOKENS.load(deps.storage, token_key)?;
+
+            token_info.push(TokenInfo {
+                token_id,
+                denom: token.denom.clone(),
+                contract_addr: token.contract_addr.as_ref().map(|addr| addr.to_string()),
+                is_native: token.is_native,
+                symbol: Some(token.symbol.clone()),
+                name: Some(token.denom.clone()),
+            });
+        }
+
+        // Convert member contributions to TokenAmount format
+        let contributions: Vec<TokenAmount> = member
+            .contribution
+            .iter()
+            .map(|(token_id, amount)| TokenAmount {
+                token_id: *token_id,
+                amount: *amount,
+                symbol: None,
+                name: None,
+            })
+            .collect();
+
+        // Convert member pool shares, and the funds they redeem for, to TokenAmount format
+        let shares: Vec<TokenAmount> = member
+            .share
+            .iter()
+            .map(|(token_id, shares)| TokenAmount {
+                token_id: *token_id,
+                amount: *shares,
+                symbol: None,
+                name: None,
+            })
+            .collect();
+
+        let balances: Vec<TokenAmount> = member
+            .share
+            .iter()
+            .map(|(token_id, shares)| TokenAmount {
+                token_id: *token_id,
+                amount: shares_value(&cooperative, *token_id, *shares),
+                symbol: None,
+                name: None,
+            })
+            .collect();
+
+        let loans = member_loans(deps, cooperative_name.clone(), member_addr)?;
+
+        Ok(MemberContributionAndShareResponse {
+            member_address,
+            cooperative_name,
+            contributions,
+            shares,
+            balances,
+            loans,
+            token_info,
+        })
+    }
+
+    pub fn get_proposal(deps: Deps, proposal_id: u64) -> StdResult<GetProposalResponse> {
+        let proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+
+        Ok(GetProposalResponse { proposal })
+    }
+
+    pub fn list_proposals(
+        deps: Deps,
+        env: Env,
+        cooperative_name: String,
+        status: Option<ProposalStatus>,
+        start_after: Option<ProposalId>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListProposalsResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+        let now = env.block.time.seconds();
+
+        let mut proposals: Vec<ProposalSummary> = Vec::new();
+        for item in PROPOSALS.idx.cooperative.prefix(cooperative_name).range(
+            deps.storage,
+            min,
+            None,
+            Order::Ascending,
+        ) {
+            if proposals.len() >= limit {
+                break;
+            }
+
+            let (_, proposal) = item?;
+            let proposal_status = proposal.status(now);
+            if status.as_ref().is_some_and(|s| *s != proposal_status) {
+                continue;
+            }
+
+            proposals.push(ProposalSummary {
+                id: proposal.id,
+                description: proposal.description,
+                proposal_type: proposal.proposal_type,
+                status: proposal_status,
+                outcome: proposal.outcome,
+                aye_count: proposal.aye_count,
+                nay_count: proposal.nay_count,
+                aye_weights: proposal.aye_weights,
+                nay_weights: proposal.nay_weights,
+                end_time: proposal.end_time,
+                executed: proposal.executed,
+            });
+        }
+
+        Ok(GetListProposalsResponse { proposals })
+    }
+
+    pub fn get_vote(
+        deps: Deps,
+        proposal_id: ProposalId,
+        voter: Addr,
+    ) -> StdResult<GetVoteResponse> {
+        let vote = VOTES.may_load(deps.storage, (proposal_id, voter))?;
+
+        Ok(GetVoteResponse { vote })
+    }
+
+    pub fn list_votes_by_voter(
+        deps: Deps,
+        voter: Addr,
+        start_after: Option<ProposalId>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListVotesResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(|id| Bound::exclusive((id, voter.clone())));
+
+        let votes = VOTES
+            .idx
+            .voter
+            .prefix(voter)
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, vote)| vote))
+            .collect::<StdResult<Vec<Vote>>>()?;
+
+        Ok(GetListVotesResponse { votes })
+    }
+
+    pub fn get_whitelisted_tokens(
+        deps: Deps,
+        cooperative_name: String,
+    ) -> StdResult<GetWhitelistedTokensResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let corporative = COOPERATIVES.load(deps.storage, cooperative_name)?;
+        let tokens = corporative.whitelisted_tokens;
+
+        Ok(GetWhitelistedTokensResponse { tokens })
+    }
+
+    pub fn list_cooperative(deps: Deps) -> StdResult<GetListCooperativesResponse> {
+        let cooperatives = COOPERATIVES
+            .keys(deps.storage, None, None, Order::Descending)
+            .collect::<Result<Vec<String>, StdError>>()?;
+
+        Ok(GetListCooperativesResponse { cooperatives })
+    }
+
+    pub fn get_token_id(deps: Deps, token: String) -> StdResult<GetTokenIdResponse> {
+        let token_id = TOKENS.load(deps.storage, token)?;
+
+        Ok(GetTokenIdResponse { token_id })
+    }
+
+    pub fn list_tokens(
+        deps: Deps,
+        start_after: Option<WhitelistedTokenId>,
+        limit: Option<u32>,
+    ) -> StdResult<GetListTokensResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let tokens = TOKEN_REGISTRY
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, token)| token))
+            .collect::<StdResult<Vec<RegisteredToken>>>()?;
+
+        Ok(GetListTokensResponse { tokens })
+    }
+
+    pub fn get_pool(
+        deps: Deps,
+        cooperative_name: String,
+        token: String,
+    ) -> StdResult<GetPoolResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name)?;
+        let token_id = TOKENS.load(deps.storage, token)?;
+
+        let total_funds = amount_of(&cooperative.total_funds, token_id);
+        let total_shares = amount_of(&cooperative.total_shares, token_id);
+        let exchange_rate = if total_shares.is_zero() {
+            Decimal::one()
+        } else {
+            Decimal::from_ratio(total_funds, total_shares)
+        };
+
+        Ok(GetPoolResponse {
+            token_id,
+            total_funds,
+            total_shares,
+            exchange_rate,
+            total_borrowed: amount_of(&cooperative.total_borrowed, token_id),
+            queued_withdrawals: amount_of(&cooperative.queued_withdrawals, token_id),
+        })
+    }
+
+    pub fn pending_rewards(
+        deps: Deps,
+        cooperative_name: String,
+        member: Addr,
+        token: String,
+    ) -> StdResult<GetPendingRewardsResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let token_id = TOKENS.load(deps.storage, token)?;
+        let member_info =
+            COOPERATIVE_MEMBERS.load(deps.storage, (cooperative_name.clone(), member.clone()))?;
+        let member_rewards =
+            load_member_rewards(deps.storage, &cooperative_name, &member, token_id)?;
+
+        // Include the rewards received since the pool index was last updated
+        let earned = match REWARDS_POOLS.may_load(deps.storage, (cooperative_name, token_id))? {
+            Some(mut pool) => {
+                accrue_rewards(&mut pool, amount_of(&cooperative.total_shares, token_id))?;
+                earned_rewards(
+                    &pool,
+                    &member_rewards,
+                    amount_of(&member_info.share, token_id),
+                )?
+            }
+            None => member_rewards.pending_rewards,
+        };
+
+        // Settlement reinvests the rewards earned since the last one for auto-compounding
+        // members, as long as they mint shares
+        let new_rewards = earned - member_rewards.pending_rewards;
+        let shares = shares_for(&cooperative, token_id, new_rewards);
+        let (pending_rewards, compounding_rewards, compounding_shares) =
+            if member_info.auto_compound && !shares.is_zero() {
+                (member_rewards.pending_rewards, new_rewards, shares)
+            } else {
+                (earned, Uint128::zero(), Uint128::zero())
+            };
+
+        Ok(GetPendingRewardsResponse {
+            token_id,
+            pending_rewards,
+            last_withdrawn_rewards: member_rewards.last_withdrawn_rewards,
+            compounding_rewards,
+            compounding_shares,
+        })
+    }
+
+    pub fn withdrawal_queue(
+        deps: Deps,
+        cooperative_name: String,
+        token: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<GetWithdrawalQueueResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        let token_id = TOKENS.load(deps.storage, token)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let min = start_after.map(Bound::exclusive);
+
+        let withdrawals = WITHDRAWAL_QUEUE
+            .prefix((cooperative_name, token_id))
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, withdrawal)| withdrawal))
+            .collect::<StdResult<Vec<QueuedWithdrawal>>>()?;
+
+        Ok(GetWithdrawalQueueResponse {
+            total_queued: amount_of(&cooperative.queued_withdrawals, token_id),
+            withdrawals,
+        })
+    }
+
+    pub fn withdrawal_position(
+        deps: Deps,
+        cooperative_name: String,
+        token: String,
+        withdrawal_id: u64,
+    ) -> StdResult<GetWithdrawalPositionResponse> {
+        let cooperative_name = cooperative_name.trim().to_lowercase();
+        let token_id = TOKENS.load(deps.storage, token)?;
+        let withdrawal = WITHDRAWAL_QUEUE.load(
+            deps.storage,
+            (cooperative_name.clone(), token_id, withdrawal_id),
+        )?;
+        let prefix = WITHDRAWAL_QUEUE.prefix((cooperative_name, token_id));
+
+        let mut position = 0;
+        let mut amount_ahead = Uint128::zero();
+        for item in prefix.range(
+            deps.storage,
+            None,
+            Some(Bound::exclusive(withdrawal_id)),
+            Order::Ascending,
+        ) {
+            let (_, ahead) = item?;
+            position += 1;
+            amount_ahead += ahead.amount;
+        }
+
+        Ok(GetWithdrawalPositionResponse {
+            withdrawal,
+            position,
+            amount_ahead,
+        })
     }
 }
 
-pub mod query {
-    use cosmwasm_std::StdError;
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, coins, Coin, Empty};
+    use cw_multi_test::{App, AppResponse, Contract, ContractWrapper, Executor};
 
-    use crate::{
-        msg::{
-            GetCooperativeResponse, GetListCooperativesResponse, GetMemberInfoResponse,
-            GetProposalResponse, GetTokenIdResponse, GetWhitelistedTokensResponse,
-            MemberContributionAndShareResponse, TokenAmount, TokenInfo,
-        },
-        state::WhitelistedTokenId,
+    use super::*;
+    use crate::msg::{
+        Cw20HookMsg, GetCooperativeResponse, GetListProposalsResponse, GetListTokensResponse,
+        GetLoanResponse, GetMemberInfoResponse, GetPendingRewardsResponse, GetPoolResponse,
+        GetProtocolFeesResponse, GetTokenIdResponse, GetVoteResponse,
+        GetWithdrawalPositionResponse, GetWithdrawalQueueResponse,
+        MemberContributionAndShareResponse,
     };
+    use crate::state::GovernanceParams;
 
-    use super::*;
+    const COOP: &str = "coop";
 
-    pub fn get_cooperative(
-        deps: Deps,
-        cooperative_name: String,
-    ) -> StdResult<GetCooperativeResponse> {
-        let cooperative_name = cooperative_name.trim().to_lowercase();
-        let corporative = COOPERATIVES.load(deps.storage, cooperative_name)?;
+    // Minimal CW20 with balances, allowances and the token info the contract queries
+    mod mock_cw20 {
+        use cosmwasm_schema::cw_serde;
+        use cosmwasm_std::{
+            to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError,
+            StdResult, Uint128,
+        };
+        use cw20::{
+            AllowanceResponse, BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg,
+            Expiration, TokenInfoResponse,
+        };
+        use cw_storage_plus::{Item, Map};
 
-        Ok(GetCooperativeResponse { corporative })
+        const DECIMALS: Item<u8> = Item::new("decimals");
+        const BALANCES: Map<&Addr, Uint128> = Map::new("balances");
+        const ALLOWANCES: Map<(&Addr, &Addr), Uint128> = Map::new("allowances");
+
+        #[cw_serde]
+        pub struct InstantiateMsg {
+            pub decimals: u8,
+            pub balances: Vec<(String, Uint128)>,
+        }
+
+        pub fn instantiate(
+            deps: DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: InstantiateMsg,
+        ) -> StdResult<Response> {
+            DECIMALS.save(deps.storage, &msg.decimals)?;
+            for (address, amount) in msg.balances {
+                BALANCES.save(deps.storage, &deps.api.addr_validate(&address)?, &amount)?;
+            }
+            Ok(Response::new())
+        }
+
+        fn move_tokens(
+            deps: &mut DepsMut,
+            from: &Addr,
+            to: &Addr,
+            amount: Uint128,
+        ) -> StdResult<()> {
+            BALANCES.update(deps.storage, from, |balance| {
+                Ok::<_, StdError>(balance.unwrap_or_default().checked_sub(amount)?)
+            })?;
+            BALANCES.update(deps.storage, to, |balance| {
+                Ok::<_, StdError>(balance.unwrap_or_default() + amount)
+            })?;
+            Ok(())
+        }
+
+        pub fn execute(
+            mut deps: DepsMut,
+            _env: Env,
+            info: MessageInfo,
+            msg: Cw20ExecuteMsg,
+        ) -> StdResult<Response> {
+            match msg {
+                Cw20ExecuteMsg::Transfer { recipient, amount } => {
+                    let recipient = deps.api.addr_validate(&recipient)?;
+                    move_tokens(&mut deps, &info.sender, &recipient, amount)?;
+                    Ok(Response::new())
+                }
+                Cw20ExecuteMsg::TransferFrom {
+                    owner,
+                    recipient,
+                    amount,
+                } => {
+                    let owner = deps.api.addr_validate(&owner)?;
+                    let recipient = deps.api.addr_validate(&recipient)?;
+                    ALLOWANCES.update(deps.storage, (&owner, &info.sender), |allowance| {
+                        Ok::<_, StdError>(allowance.unwrap_or_default().checked_sub(amount)?)
+                    })?;
+                    move_tokens(&mut deps, &owner, &recipient, amount)?;
+                    Ok(Response::new())
+                }
+                Cw20ExecuteMsg::Send {
+                    contract,
+                    amount,
+                    msg,
+                } => {
+                    let contract = deps.api.addr_validate(&contract)?;
+                    move_tokens(&mut deps, &info.sender, &contract, amount)?;
+                    let receive = Cw20ReceiveMsg {
+                        sender: info.sender.to_string(),
+                        amount,
+                        msg,
+                    };
+                    Ok(Response::new().add_message(receive.into_cosmos_msg(contract)?))
+                }
+                Cw20ExecuteMsg::IncreaseAllowance {
+                    spender, amount, ..
+                } => {
+                    let spender = deps.api.addr_validate(&spender)?;
+                    ALLOWANCES.update(deps.storage, (&info.sender, &spender), |allowance| {
+                        Ok::<_, StdError>(allowance.unwrap_or_default() + amount)
+                    })?;
+                    Ok(Response::new())
+                }
+                _ => Err(StdError::generic_err("not supported")),
+            }
+        }
+
+        pub fn query(deps: Deps, _env: Env, msg: Cw20QueryMsg) -> StdResult<Binary> {
+            match msg {
+                Cw20QueryMsg::Balance { address } => {
+                    let address = deps.api.addr_validate(&address)?;
+                    to_json_binary(&BalanceResponse {
+                        balance: BALANCES
+                            .may_load(deps.storage, &address)?
+                            .unwrap_or_default(),
+                    })
+                }
+                Cw20QueryMsg::Allowance { owner, spender } => {
+                    let owner = deps.api.addr_validate(&owner)?;
+                    let spender = deps.api.addr_validate(&spender)?;
+                    to_json_binary(&AllowanceResponse {
+                        allowance: ALLOWANCES
+                            .may_load(deps.storage, (&owner, &spender))?
+                            .unwrap_or_default(),
+                        expires: Expiration::Never {},
+                    })
+                }
+                Cw20QueryMsg::TokenInfo {} => to_json_binary(&TokenInfoResponse {
+                    name: "Token".into(),
+                    symbol: "TOK".into(),
+                    decimals: DECIMALS.load(deps.storage)?,
+                    total_supply: Uint128::zero(),
+                }),
+                _ => Err(StdError::generic_err("not supported")),
+            }
+        }
     }
 
-    pub fn get_member_info(
-        deps: Deps,
-        cooperative_name: String,
-        member: Addr,
-    ) -> StdResult<GetMemberInfoResponse> {
-        let cooperative_name = cooperative_name.trim().to_lowercase();
-        let corporative = COOPERATIVES.load(deps.storage, cooperative_name)?;
+    // Writes the raw storage of a 0.1.0 deployment, to be migrated to the current code
+    mod legacy {
+        use cosmwasm_schema::cw_serde;
+        use cosmwasm_std::{
+            Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError, StdResult,
+        };
+        use cw_storage_plus::Map;
 
-        let member_idx = corporative.members.iter().position(|x| x.address == member);
+        #[cw_serde]
+        pub struct InstantiateMsg {
+            pub member: String,
+            pub cw20: String,
+        }
 
-        if member_idx.is_none() {
-            return Ok(GetMemberInfoResponse {
-                info: Member {
-                    address: Addr::unchecked("0"),
-                    contribution: vec![],
-                    share: vec![],
-                    joined_at: 0,
-                    reputation_score: Decimal::zero(),
-                    loans: vec![],
-                },
-            });
+        pub fn instantiate(
+            deps: DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: InstantiateMsg,
+        ) -> StdResult<Response> {
+            let InstantiateMsg { member, cw20 } = msg;
+            cw2::set_contract_version(deps.storage, "crates.io:ajor", "0.1.0")?;
+
+            // Token ids were positions in the cooperative whitelist
+            let state = r#"{"owner":"owner","weight_token":"untrn","total_corporatives":1,"total_pooled_funds":[[0,"5"],[1,"7"]],"current_proposal_id":1,"current_whitelisted_token_id":1,"current_loan_id":0}"#;
+            let cooperative = format!(
+                r#"{{"name":"coop","total_funds":[[0,"5"],[1,"7"]],"members":[{{"address":"{member}","contribution":[[0,"5"],[1,"7"]],"share":[],"joined_at":1,"reputation_score":"0","loans":[]}}],"risk_profile":{{"interest_rate":"0.1","collateralization_ratio":"1"}},"whitelisted_tokens":[{{"denom":"untrn","contract_addr":null,"is_native":true,"max_loan_ratio":"0.5"}},{{"denom":"TOK","contract_addr":"{cw20}","is_native":false,"max_loan_ratio":"0.5"}}]}}"#
+            );
+            let proposal = format!(
+                r#"{{"id":1,"description":"add","data":{{"denom":null,"token_addr":null,"is_native":null,"max_loan_ratio":null,"new_member_addr":"{member}"}},"votes":[{{"voter":"{member}","conviction":"7","voted_at":3}}],"aye_count":1,"nay_count":0,"aye_weights":7,"nay_weights":0,"end_time":100,"quorum":null,"proposal_type":"AddMember","outcome":null,"executed":false}}"#
+            );
+
+            let by_name = |namespace: &'static str, key: &str| {
+                Map::<&str, Empty>::new(namespace).key(key).to_vec()
+            };
+            let by_id = |namespace: &'static str, key: u64| {
+                Map::<u64, Empty>::new(namespace).key(key).to_vec()
+            };
+            deps.storage.set(b"state", state.as_bytes());
+            deps.storage
+                .set(&by_name("cooperatives", "coop"), cooperative.as_bytes());
+            deps.storage.set(&by_name("tokens", "NATIVE"), b"1");
+            deps.storage.set(&by_name("tokens", &cw20), b"1");
+            deps.storage.set(
+                &by_id("prices", 1),
+                br#"{"latest_price_to_usd":"2","last_updated_at":"1"}"#,
+            );
+            deps.storage
+                .set(&by_name("cooperatives_proposals", "coop"), b"[1]");
+            deps.storage
+                .set(&by_id("proposals", 1), proposal.as_bytes());
+
+            Ok(Response::new())
         }
 
-        let member_idx = member_idx.unwrap();
-        let member_data = corporative.members.get(member_idx);
+        pub fn execute(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+            Err(StdError::generic_err("legacy fixture"))
+        }
 
-        if member_data.is_none() {
-            return Ok(GetMemberInfoResponse {
-                info: Member {
-                    address: Addr::unchecked("0"),
+        pub fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+            Err(StdError::generic_err("legacy fixture"))
+        }
+    }
+
+    struct Suite {
+        app: App,
+        code_id: u64,
+        contract: Addr,
+        cw20: Addr,
+        owner: Addr,
+        members: Vec<Addr>,
+    }
+
+    fn contract() -> Box<dyn Contract<Empty>> {
+        Box::new(
+            ContractWrapper::new(execute, instantiate, query)
+                .with_sudo(sudo)
+                .with_migrate(migrate),
+        )
+    }
+
+    fn cw20_contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(
+            mock_cw20::execute,
+            mock_cw20::instantiate,
+            mock_cw20::query,
+        ))
+    }
+
+    fn instantiate_cw20(app: &mut App, owner: &Addr, decimals: u8, holders: &[Addr]) -> Addr {
+        let code_id = app.store_code(cw20_contract());
+        let msg = mock_cw20::InstantiateMsg {
+            decimals,
+            balances: holders
+                .iter()
+                .map(|holder| (holder.to_string(), Uint128::new(10u128.pow(24))))
+                .collect(),
+        };
+        app.instantiate_contract(code_id, owner.clone(), &msg, &[], "token", None)
+            .unwrap()
+    }
+
+    fn native(denom: &str) -> WhitelistedToken {
+        WhitelistedToken {
+            denom: denom.into(),
+            contract_addr: None,
+            is_native: true,
+            max_loan_ratio: Decimal::percent(50),
+            decimals: 6,
+            symbol: denom.to_uppercase(),
+        }
+    }
+
+    // A cooperative of three members lending uatom, untrn and a CW20, all priced at 1 USD,
+    // with 10% of the interest going to the protocol
+    fn setup() -> Suite {
+        let mut app = App::default();
+        let owner = app.api().addr_make("owner");
+        let members: Vec<Addr> = ["m1", "m2", "m3"]
+            .iter()
+            .map(|name| app.api().addr_make(name))
+            .collect();
+        app.init_modules(|router, _, storage| {
+            for member in &members {
+                let balance = vec![coin(10_000, "uatom"), coin(10_000, "untrn")];
+                router.bank.init_balance(storage, member, balance).unwrap();
+            }
+        });
+        let cw20 = instantiate_cw20(&mut app, &owner, 6, &members);
+
+        let code_id = app.store_code(contract());
+        let msg = InstantiateMsg {
+            owner: None,
+            guardian: None,
+            weight_token: "untrn".into(),
+            protocol_fee: Decimal::percent(10),
+            max_members_per_cooperative: 10,
+            max_tokens_per_cooperative: 5,
+            governance: GovernanceParams {
+                quorum: Decimal::percent(50),
+                voting_period: 1000,
+                vote_lock_period: 100,
+            },
+        };
+        let contract = app
+            .instantiate_contract(
+                code_id,
+                owner.clone(),
+                &msg,
+                &[],
+                "ajor",
+                Some(owner.to_string()),
+            )
+            .unwrap();
+
+        let create = ExecuteMsg::CreateCooperative {
+            name: "Coop".into(),
+            risk_profile: RiskProfile {
+                interest_rate: Decimal::percent(10),
+                collateralization_ratio: Decimal::one(),
+            },
+            initial_members: members
+                .iter()
+                .map(|address| Member {
+                    address: address.clone(),
                     contribution: vec![],
                     share: vec![],
                     joined_at: 0,
                     reputation_score: Decimal::zero(),
-                    loans: vec![],
+                    auto_compound: false,
+                })
+                .collect(),
+            initial_whitelisted_tokens: vec![
+                native("uatom"),
+                native("untrn"),
+                WhitelistedToken {
+                    denom: "TOK".into(),
+                    contract_addr: Some(cw20.clone()),
+                    is_native: false,
+                    max_loan_ratio: Decimal::percent(50),
+                    decimals: 6,
+                    symbol: "TOK".into(),
                 },
-            });
+            ],
+            proposal_deposit: None,
+        };
+        app.execute_contract(owner.clone(), contract.clone(), &create, &[])
+            .unwrap();
+        for token in ["uatom", "untrn", cw20.as_str()] {
+            let msg = ExecuteMsg::UpdateTokenPrice {
+                token_addr: token.into(),
+                usd_price: Decimal::one(),
+            };
+            app.execute_contract(owner.clone(), contract.clone(), &msg, &[])
+                .unwrap();
         }
 
-        let info = member_data.unwrap();
-        Ok(GetMemberInfoResponse { info: info.clone() })
+        Suite {
+            app,
+            code_id,
+            contract,
+            cw20,
+            owner,
+            members,
+        }
     }
 
-    pub fn query_member_contribution_and_share(
-        deps: Deps,
-        cooperative_name: String,
-        member_address: String,
-    ) -> StdResult<MemberContributionAndShareResponse> {
-        let cooperative_name = cooperative_name.trim().to_lowercase();
-        let member_addr = deps.api.addr_validate(&member_address)?;
+    impl Suite {
+        fn member_addr(&self, index: usize) -> Addr {
+            self.members[index].clone()
+        }
 
-        // Get the cooperative
-        let cooperative = COOPERATIVES.load(deps.storage, cooperative_name.clone())?;
+        fn execute(&mut self, sender: &Addr, msg: &ExecuteMsg, funds: &[Coin]) -> AppResponse {
+            self.app
+                .execute_contract(sender.clone(), self.contract.clone(), msg, funds)
+                .unwrap()
+        }
 
-        // Find the member in the cooperative
-        let member = cooperative
-            .members
-            .iter()
-            .find(|m| m.address == member_addr)
-            .ok_or_else(|| {
-                cosmwasm_std::StdError::generic_err(format!(
-                    "Member {} not found in cooperative {}",
-                    member_address, cooperative_name
-                ))
-            })?;
+        fn execute_err(
+            &mut self,
+            sender: &Addr,
+            msg: &ExecuteMsg,
+            funds: &[Coin],
+        ) -> ContractError {
+            self.app
+                .execute_contract(sender.clone(), self.contract.clone(), msg, funds)
+                .unwrap_err()
+                .downcast()
+                .unwrap()
+        }
 
-        // Collect token information for all tokens in the cooperative
-        let mut token_info: Vec<TokenInfo> = Vec::new();
-        for token in &cooperative.whitelisted_tokens {
-            let token_id = if token.is_native {
-                TOKENS.load(deps.storage, Addr::unchecked("NATIVE"))?
-            } else {
-                TOKENS.load(deps.storage, token.contract_addr.as_ref().unwrap().clone())?
+        fn query<T: serde::de::DeserializeOwned>(&self, msg: &QueryMsg) -> T {
+            self.app
+                .wrap()
+                .query_wasm_smart(&self.contract, msg)
+                .unwrap()
+        }
+
+        fn fund(&mut self, sender: &Addr, denom: &str, amount: u128) -> AppResponse {
+            let msg = ExecuteMsg::FundCooperative {
+                cooperative_name: COOP.into(),
+                token: denom.into(),
+                is_native: true,
+                amount: Uint128::new(amount),
             };
+            self.execute(sender, &msg, &coins(amount, denom))
+        }
 
-            token_info.push(TokenInfo {
-                token_id,
-                denom: token.denom.clone(),
-                contract_addr: token.contract_addr.as_ref().map(|addr| addr.to_string()),
-                is_native: token.is_native,
-                symbol: None,
-                name: Some(token.denom.clone()),
-            });
+        // Sends CW20 tokens to the contract with a hook message
+        fn send(&mut self, sender: &Addr, amount: u128, hook: &Cw20HookMsg) -> AppResponse {
+            let msg = cw20::Cw20ExecuteMsg::Send {
+                contract: self.contract.to_string(),
+                amount: Uint128::new(amount),
+                msg: to_json_binary(hook).unwrap(),
+            };
+            self.app
+                .execute_contract(sender.clone(), self.cw20.clone(), &msg, &[])
+                .unwrap()
         }
 
-        // Convert member contributions to TokenAmount format
-        let contributions: Vec<TokenAmount> = member
-            .contribution
-            .iter()
-            .map(|(token_id, amount)| TokenAmount {
-                token_id: *token_id,
-                amount: *amount,
-                symbol: None,
-                name: None,
+        fn balance(&self, address: &Addr, denom: &str) -> u128 {
+            self.app
+                .wrap()
+                .query_balance(address, denom)
+                .unwrap()
+                .amount
+                .u128()
+        }
+
+        fn cw20_balance(&self, address: &Addr) -> u128 {
+            let res: cw20::BalanceResponse = self
+                .app
+                .wrap()
+                .query_wasm_smart(
+                    &self.cw20,
+                    &cw20::Cw20QueryMsg::Balance {
+                        address: address.to_string(),
+                    },
+                )
+                .unwrap();
+            res.balance.u128()
+        }
+
+        fn pool(&self, token: &str) -> GetPoolResponse {
+            self.query(&QueryMsg::Pool {
+                cooperative_name: COOP.into(),
+                token: token.into(),
             })
-            .collect();
+        }
 
-        // Calculate up-to-date shares for each token the member has contributed to
-        let mut calculated_shares: Vec<(WhitelistedTokenId, Uint128)> = Vec::new();
+        fn member(&self, address: &Addr) -> Member {
+            let res: GetMemberInfoResponse = self.query(&QueryMsg::GetMemberInfo {
+                cooperative_name: COOP.into(),
+                member: address.clone(),
+            });
+            res.info
+        }
 
-        for &(token_id, contribution_amount) in &member.contribution {
-            // Get the total funds for this token in the cooperative (including accumulated interest)
-            let total_token_funds = cooperative
-                .total_funds
-                .iter()
-                .find(|(t_id, _)| t_id == &token_id)
-                .map(|(_, amount)| *amount)
-                .unwrap_or(Uint128::zero());
+        fn pending_rewards(&self, address: &Addr) -> GetPendingRewardsResponse {
+            self.query(&QueryMsg::PendingRewards {
+                cooperative_name: COOP.into(),
+                member: address.clone(),
+                token: self.cw20.to_string(),
+            })
+        }
+    }
 
-            // If there are no funds for this token, share is zero
-            if total_token_funds.is_zero() {
-                calculated_shares.push((token_id, Uint128::zero()));
-                continue;
-            }
+    fn borrow(collateral: &str, amount: u128, token_out: &str) -> ExecuteMsg {
+        ExecuteMsg::Borrow {
+            cooperative_name: COOP.into(),
+            tokens_in: vec![collateral.into()],
+            amount_in: vec![Uint128::new(amount)],
+            token_out: token_out.into(),
+            min_amount_out: Uint128::zero(),
+        }
+    }
 
-            // Calculate the sum of all member contributions for this token
-            let total_member_contributions_for_token: Uint128 = cooperative
-                .members
-                .iter()
-                .flat_map(|m| m.contribution.iter())
-                .filter_map(|&(t_id, amount)| if t_id == token_id { Some(amount) } else { None })
-                .sum();
-
-            // Calculate the member's percentage of contributions
-            // Use Decimal for precise percentage calculation
-            let contribution_percentage = if !total_member_contributions_for_token.is_zero() {
-                Decimal::from_ratio(contribution_amount, total_member_contributions_for_token)
-            } else {
-                Decimal::zero()
-            };
+    fn repay(token: &str) -> ExecuteMsg {
+        ExecuteMsg::Repay {
+            cooperative_name: COOP.into(),
+            token: token.into(),
+        }
+    }
+
+    fn withdraw(token: &str, amount: Option<u128>) -> ExecuteMsg {
+        ExecuteMsg::WithdrawContributionAndReward {
+            cooperative_name: COOP.into(),
+            token: token.into(),
+            amount: amount.map(Uint128::new),
+            rewards_only: None,
+        }
+    }
 
-            // Calculate member's share of the total funds (including interest)
-            // Share = (member's contribution percentage) * total cooperative funds for this token
-            let share_amount = contribution_percentage.to_uint_ceil() * total_token_funds;
+    fn cancel_withdrawal(withdrawal_id: u64) -> ExecuteMsg {
+        ExecuteMsg::CancelWithdrawal {
+            cooperative_name: COOP.into(),
+            token: "untrn".into(),
+            withdrawal_id,
+        }
+    }
 
-            calculated_shares.push((token_id, share_amount));
+    fn claim(token: &Addr) -> ExecuteMsg {
+        ExecuteMsg::ClaimRewards {
+            cooperative_name: COOP.into(),
+            token: token.to_string(),
         }
+    }
 
-        // Convert calculated shares to TokenAmount format
-        let shares: Vec<TokenAmount> = calculated_shares
+    // Value of an attribute emitted by the contract
+    fn attr(res: &AppResponse, key: &str) -> String {
+        res.events
             .iter()
-            .map(|(token_id, amount)| TokenAmount {
-                token_id: *token_id,
-                amount: *amount,
-                symbol: None,
-                name: None,
-            })
-            .collect();
+            .flat_map(|event| &event.attributes)
+            .find(|attribute| attribute.key == key)
+            .map(|attribute| attribute.value.clone())
+            .unwrap()
+    }
 
-        Ok(MemberContributionAndShareResponse {
-            member_address,
-            cooperative_name,
-            contributions,
-            shares,
-            loans: member.loans.clone(),
-            token_info,
-        })
+    // m1 borrows 1000 untrn against 1000 of its 2000 uatom and repays them with 1% interest
+    fn lend_untrn(suite: &mut Suite) {
+        let (m1, m2) = (suite.member_addr(0), suite.member_addr(1));
+        suite.fund(&m1, "uatom", 2000);
+        suite.fund(&m2, "untrn", 1000);
+        suite.execute(&m1, &borrow("uatom", 1000, "untrn"), &[]);
+        suite.execute(&m1, &repay("untrn"), &coins(1010, "untrn"));
     }
 
-    pub fn get_proposal(deps: Deps, proposal_id: u64) -> StdResult<GetProposalResponse> {
-        let proposal = PROPOSALS.load(deps.storage, proposal_id)?;
+    #[test]
+    fn deposit_borrow_repay_withdraw() {
+        let mut suite = setup();
+        let (m1, m2) = (suite.member_addr(0), suite.member_addr(1));
+        suite.fund(&m1, "uatom", 2000);
+        let res = suite.fund(&m2, "untrn", 1000);
+        assert_eq!(attr(&res, "shares"), "1000");
+
+        // The borrowed amount is covered by locked pool shares of the collateral
+        let res = suite.execute(&m1, &borrow("uatom", 1000, "untrn"), &[]);
+        assert_eq!(attr(&res, "amount_out"), "1000");
+        assert_eq!(suite.balance(&m1, "untrn"), 11_000);
+        let loan: GetLoanResponse = suite.query(&QueryMsg::GetLoan { loan_id: 0 });
+        assert_eq!(loan.loan.collateral_shares, vec![Uint128::new(1000)]);
+
+        let err = suite.execute_err(&m1, &withdraw("uatom", Some(1001)), &[]);
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let err = suite.execute_err(&m1, &borrow("uatom", 1, "untrn"), &[]);
+        assert!(matches!(err, ContractError::InsufficientPoolFunds {}));
+
+        let err = suite.execute_err(&m1, &repay("untrn"), &coins(1000, "untrn"));
+        assert!(matches!(err, ContractError::FundsMustMatchAmount {}));
+        let res = suite.execute(&m1, &repay("untrn"), &coins(1010, "untrn"));
+        assert_eq!(attr(&res, "interest"), "10");
+        let pool = suite.pool("untrn");
+        assert_eq!(pool.total_borrowed, Uint128::zero());
+
+        // Repaying released the collateral, and the lender redeems principal and interest
+        let res = suite.execute(&m1, &withdraw("uatom", None), &[]);
+        assert_eq!(attr(&res, "amount"), "2000");
+        let res = suite.execute(&m2, &withdraw("untrn", None), &[]);
+        assert_eq!(attr(&res, "amount"), "1009");
+        assert_eq!(attr(&res, "interest"), "9");
+        assert_eq!(suite.balance(&m1, "uatom"), 10_000);
+        assert_eq!(suite.balance(&m2, "untrn"), 10_009);
+        assert!(suite
+            .member(&m2)
+            .share
+            .iter()
+            .all(|(_, shares)| shares.is_zero()));
 
-        Ok(GetProposalResponse { proposal })
+        let err = suite.execute_err(&m2, &withdraw("untrn", None), &[]);
+        assert!(matches!(err, ContractError::NoContribution {}));
     }
 
-    pub fn get_whitelisted_tokens(
-        deps: Deps,
-        cooperative_name: String,
-    ) -> StdResult<GetWhitelistedTokensResponse> {
-        let cooperative_name = cooperative_name.trim().to_lowercase();
-        let corporative = COOPERATIVES.load(deps.storage, cooperative_name)?;
-        let tokens = corporative.whitelisted_tokens;
+    #[test]
+    fn share_rounding_and_interest_growth() {
+        let mut suite = setup();
+        let (m2, m3) = (suite.member_addr(1), suite.member_addr(2));
+        lend_untrn(&mut suite);
+
+        // The pool interest raises the exchange rate of the existing shares
+        let pool = suite.pool("untrn");
+        assert_eq!(
+            (pool.total_funds, pool.total_shares),
+            (Uint128::new(1009), Uint128::new(1000))
+        );
+        assert_eq!(pool.exchange_rate, Decimal::permille(1009));
+
+        // Shares are minted and redeemed rounding down, in favour of the pool
+        let res = suite.fund(&m3, "untrn", 100);
+        assert_eq!(attr(&res, "shares"), "99");
+        let res: MemberContributionAndShareResponse =
+            suite.query(&QueryMsg::MemberContributionAndShare {
+                cooperative_name: COOP.into(),
+                member_address: m3.clone(),
+            });
+        assert_eq!(res.balances[0].amount, Uint128::new(99));
+
+        let err = suite.execute_err(&m3, &withdraw("untrn", Some(100)), &[]);
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        let res = suite.execute(&m3, &withdraw("untrn", None), &[]);
+        assert_eq!(attr(&res, "amount"), "99");
+
+        // The rounding is left to the remaining shares
+        let res = suite.execute(&m2, &withdraw("untrn", None), &[]);
+        assert_eq!(attr(&res, "amount"), "1010");
+        let pool = suite.pool("untrn");
+        assert_eq!(
+            (pool.total_funds, pool.total_shares),
+            (Uint128::zero(), Uint128::zero())
+        );
+    }
 
-        Ok(GetWhitelistedTokensResponse { tokens })
+    #[test]
+    fn protocol_fee_split() {
+        let mut suite = setup();
+        let (owner, m1, m3) = (
+            suite.owner.clone(),
+            suite.member_addr(0),
+            suite.member_addr(2),
+        );
+        lend_untrn(&mut suite);
+
+        // 10% of the 10 interest goes to the treasury, the rest to the pool
+        let token: GetTokenIdResponse = suite.query(&QueryMsg::GetTokenId {
+            token: "untrn".into(),
+        });
+        let fees: GetProtocolFeesResponse = suite.query(&QueryMsg::ProtocolFees {});
+        assert_eq!(fees.fees, vec![(token.token_id, Uint128::new(1))]);
+        assert_eq!(suite.pool("untrn").total_funds, Uint128::new(1009));
+
+        let withdraw_fees = |amount: Option<u128>| ExecuteMsg::WithdrawProtocolFees {
+            token: "untrn".into(),
+            amount: amount.map(Uint128::new),
+            recipient: Some(m3.to_string()),
+        };
+        let err = suite.execute_err(&m1, &withdraw_fees(None), &[]);
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = suite.execute_err(&owner, &withdraw_fees(Some(2)), &[]);
+        assert!(matches!(err, ContractError::InsufficientFunds {}));
+        suite.execute(&owner, &withdraw_fees(None), &[]);
+        assert_eq!(suite.balance(&m3, "untrn"), 10_001);
+        let err = suite.execute_err(&owner, &withdraw_fees(None), &[]);
+        assert!(matches!(err, ContractError::NoFunds {}));
     }
 
-    pub fn list_cooperative(deps: Deps) -> StdResult<GetListCooperativesResponse> {
-        let cooperatives = COOPERATIVES
-            .keys(deps.storage, None, None, Order::Descending)
-            .collect::<Result<Vec<String>, StdError>>()?;
+    #[test]
+    fn withdrawal_queue_fills_in_order_and_cancel_restores_shares() {
+        let mut suite = setup();
+        let (m1, m2, m3) = (
+            suite.member_addr(0),
+            suite.member_addr(1),
+            suite.member_addr(2),
+        );
+        suite.fund(&m1, "uatom", 2000);
+        suite.fund(&m2, "untrn", 600);
+        suite.fund(&m3, "untrn", 400);
+        suite.execute(&m1, &borrow("uatom", 900, "untrn"), &[]);
+
+        // Only 100 untrn are left in the pool, the rest of the withdrawal is queued
+        let res = suite.execute(&m2, &withdraw("untrn", None), &[]);
+        assert_eq!(attr(&res, "withdrawal_id"), "0");
+        assert_eq!(attr(&res, "queue_filled"), "100");
+        assert_eq!(suite.balance(&m2, "untrn"), 9500);
+
+        // Later withdrawals queue behind it
+        let res = suite.execute(&m3, &withdraw("untrn", None), &[]);
+        assert_eq!(attr(&res, "withdrawal_id"), "1");
+        assert_eq!(attr(&res, "queue_filled"), "0");
+        let position = |suite: &Suite| -> GetWithdrawalPositionResponse {
+            suite.query(&QueryMsg::WithdrawalPosition {
+                cooperative_name: COOP.into(),
+                token: "untrn".into(),
+                withdrawal_id: 1,
+            })
+        };
+        let res = position(&suite);
+        assert_eq!((res.position, res.amount_ahead), (1, Uint128::new(500)));
+
+        // Deposits fill the head of the queue first
+        let res = suite.fund(&m1, "untrn", 200);
+        assert_eq!(attr(&res, "queue_filled"), "200");
+        let queue: GetWithdrawalQueueResponse = suite.query(&QueryMsg::WithdrawalQueue {
+            cooperative_name: COOP.into(),
+            token: "untrn".into(),
+            start_after: None,
+            limit: None,
+        });
+        assert_eq!(queue.total_queued, Uint128::new(700));
+        let head = &queue.withdrawals[0];
+        assert_eq!(
+            (head.amount, head.shares, head.principal),
+            (Uint128::new(300), Uint128::new(300), Uint128::new(300))
+        );
 
-        Ok(GetListCooperativesResponse { cooperatives })
+        // Cancelling gives back the shares and principal of the unpaid amount
+        let err = suite.execute_err(&m3, &cancel_withdrawal(0), &[]);
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let res = suite.execute(&m2, &cancel_withdrawal(0), &[]);
+        assert_eq!(attr(&res, "shares"), "300");
+        let member = suite.member(&m2);
+        assert_eq!(member.share, member.contribution);
+        assert_eq!(
+            member
+                .share
+                .iter()
+                .map(|(_, shares)| shares.u128())
+                .sum::<u128>(),
+            300
+        );
+        let res = position(&suite);
+        assert_eq!((res.position, res.amount_ahead), (0, Uint128::zero()));
+
+        // The repayment pays the remaining withdrawal
+        let res = suite.execute(&m1, &repay("untrn"), &coins(909, "untrn"));
+        assert_eq!(attr(&res, "queue_filled"), "400");
+        assert_eq!(suite.balance(&m3, "untrn"), 10_000);
+        let pool = suite.pool("untrn");
+        assert_eq!(
+            (pool.total_funds, pool.total_shares, pool.queued_withdrawals),
+            (Uint128::new(509), Uint128::new(500), Uint128::zero())
+        );
     }
 
-    pub fn get_token_id(deps: Deps, token: String) -> StdResult<GetTokenIdResponse> {
-        let token_id = TOKENS.load(deps.storage, Addr::unchecked(token))?;
+    #[test]
+    fn reward_remainders_are_carried() {
+        let mut suite = setup();
+        let (m1, m2) = (suite.member_addr(0), suite.member_addr(1));
+        let cw20 = suite.cw20.clone();
+        let contribute = Cw20HookMsg::Contribute {
+            cooperative_name: COOP.into(),
+        };
+        suite.send(
+            &m1,
+            10u128.pow(21),
+            &Cw20HookMsg::Fund {
+                cooperative_name: COOP.into(),
+            },
+        );
 
-        Ok(GetTokenIdResponse { token_id })
+        // Too small for the reward index, so kept for the next contribution
+        suite.send(&m2, 100, &contribute);
+        assert_eq!(suite.pending_rewards(&m1).pending_rewards, Uint128::zero());
+        suite.send(&m2, 1000, &contribute);
+        assert_eq!(
+            suite.pending_rewards(&m1).pending_rewards,
+            Uint128::new(1000)
+        );
+        suite.send(&m2, 900, &contribute);
+        assert_eq!(
+            suite.pending_rewards(&m1).pending_rewards,
+            Uint128::new(2000)
+        );
+
+        let before = suite.cw20_balance(&m1);
+        let res = suite.execute(&m1, &claim(&cw20), &[]);
+        assert_eq!(attr(&res, "rewards"), "2000");
+        assert_eq!(suite.cw20_balance(&m1), before + 2000);
+        let err = suite.execute_err(&m1, &claim(&cw20), &[]);
+        assert!(matches!(err, ContractError::NoRewardsAvailable {}));
     }
-}
 
-#[cfg(test)]
-mod tests {}
+    #[test]
+    fn auto_compound_reinvests_new_rewards() {
+        let mut suite = setup();
+        let (m1, m2, m3) = (
+            suite.member_addr(0),
+            suite.member_addr(1),
+            suite.member_addr(2),
+        );
+        let cw20 = suite.cw20.clone();
+        let fund = Cw20HookMsg::Fund {
+            cooperative_name: COOP.into(),
+        };
+        let contribute = Cw20HookMsg::Contribute {
+            cooperative_name: COOP.into(),
+        };
+        suite.send(&m1, 50, &fund);
+        suite.send(&m2, 50, &fund);
+        suite.send(&m3, 50, &contribute);
+
+        // Rewards earned before enabling stay claimable
+        let msg = ExecuteMsg::SetAutoCompound {
+            cooperative_name: COOP.into(),
+            enabled: true,
+        };
+        suite.execute(&m2, &msg, &[]);
+        assert_eq!(suite.pending_rewards(&m2).pending_rewards, Uint128::new(25));
+        let res = suite.execute(&m2, &claim(&cw20), &[]);
+        assert_eq!(
+            (attr(&res, "rewards"), attr(&res, "compounded")),
+            ("25".into(), "0".into())
+        );
+
+        // New rewards await compounding until the member's rewards are next settled
+        suite.send(&m3, 50, &contribute);
+        let pending = suite.pending_rewards(&m2);
+        assert_eq!(
+            (
+                pending.pending_rewards,
+                pending.compounding_rewards,
+                pending.compounding_shares
+            ),
+            (Uint128::zero(), Uint128::new(25), Uint128::new(25))
+        );
+        let res = suite.execute(&m2, &claim(&cw20), &[]);
+        assert_eq!(attr(&res, "compounded"), "25");
+
+        let member = suite.member(&m2);
+        assert_eq!(member.contribution, member.share);
+        let pool = suite.pool(cw20.as_str());
+        assert_eq!(
+            (pool.total_funds, pool.total_shares),
+            (Uint128::new(125), Uint128::new(125))
+        );
+
+        // m1 did not compound and claims both contributions' rewards
+        let res = suite.execute(&m1, &claim(&cw20), &[]);
+        assert_eq!(attr(&res, "rewards"), "50");
+    }
+
+    #[test]
+    fn migrate_from_0_1_0() {
+        let mut suite = setup();
+        let (owner, m1) = (suite.owner.clone(), suite.member_addr(0));
+        let cw20 = instantiate_cw20(&mut suite.app, &owner, 8, &[]);
+        let missing_cw20 = suite.app.api().addr_make("missing");
+        let legacy_code = suite.app.store_code(Box::new(ContractWrapper::new(
+            legacy::execute,
+            legacy::instantiate,
+            legacy::query,
+        )));
+        let mut deploy = |cw20: &Addr| {
+            let msg = legacy::InstantiateMsg {
+                member: m1.to_string(),
+                cw20: cw20.to_string(),
+            };
+            suite
+                .app
+                .instantiate_contract(
+                    legacy_code,
+                    owner.clone(),
+                    &msg,
+                    &[],
+                    "legacy",
+                    Some(owner.to_string()),
+                )
+                .unwrap()
+        };
+        let missing = deploy(&missing_cw20);
+        let legacy = deploy(&cw20);
+
+        // A CW20 that can't report its decimals fails the migration
+        let err: ContractError = suite
+            .app
+            .migrate_contract(
+                owner.clone(),
+                missing.clone(),
+                &MigrateMsg {},
+                suite.code_id,
+            )
+            .unwrap_err()
+            .downcast()
+            .unwrap();
+        assert!(matches!(err, ContractError::MigrationFailed { .. }));
+
+        suite
+            .app
+            .migrate_contract(owner.clone(), legacy.clone(), &MigrateMsg {}, suite.code_id)
+            .unwrap();
+        suite.contract = legacy;
+
+        // Tokens are registered with their own decimals, native coins with 6
+        let res: GetCooperativeResponse = suite.query(&QueryMsg::GetCooperative {
+            cooperative_name: COOP.into(),
+        });
+        let decimals: Vec<u8> = res
+            .corporative
+            .whitelisted_tokens
+            .iter()
+            .map(|t| t.decimals)
+            .collect();
+        assert_eq!(decimals, vec![6, 8]);
+        let res: GetListTokensResponse = suite.query(&QueryMsg::ListTokens {
+            start_after: None,
+            limit: None,
+        });
+        assert_eq!(res.tokens.len(), 2);
+
+        // Balances are rekeyed by registry id and backed by pool shares
+        let member = suite.member(&m1);
+        assert_eq!(
+            member.contribution,
+            vec![(1, Uint128::new(5)), (2, Uint128::new(7))]
+        );
+        assert_eq!(member.share, member.contribution);
+        let pool = suite.pool(cw20.as_str());
+        assert_eq!(
+            (pool.total_funds, pool.total_shares),
+            (Uint128::new(7), Uint128::new(7))
+        );
+
+        // Proposals are listed through the cooperative index with their votes moved out
+        let res: GetListProposalsResponse = suite.query(&QueryMsg::ListProposals {
+            cooperative_name: COOP.into(),
+            status: None,
+            start_after: None,
+            limit: None,
+        });
+        assert_eq!(res.proposals.len(), 1);
+        assert_eq!(res.proposals[0].aye_weights, Uint128::new(7));
+        let res: GetVoteResponse = suite.query(&QueryMsg::GetVote {
+            proposal_id: 1,
+            voter: m1.clone(),
+        });
+        assert_eq!(res.vote.unwrap().weight, Uint128::new(7));
+    }
+}
@@ -1,6 +1,8 @@
 use cosmwasm_std::StdError;
 use thiserror::Error;
 
+use crate::state::PausableOperation;
+
 #[derive(Error, Debug)]
 pub enum ContractError {
     #[error("{0}")]
@@ -36,12 +38,39 @@ pub enum ContractError {
     #[error("Invalid token")]
     InvalidToken {},
 
+    #[error("Invalid whitelisted token: {msg}")]
+    InvalidWhitelistedToken { msg: String },
+
     #[error("Invalid input")]
     InvalidInput {},
 
     #[error("Invalid collateral")]
     InvalidCollateral { msg: String },
 
+    #[error("Invalid config: {msg}")]
+    InvalidConfig { msg: String },
+
+    #[error("Migration failed: {msg}")]
+    MigrationFailed { msg: String },
+
+    #[error("Cooperative frozen")]
+    CooperativeFrozen {},
+
+    #[error("{operation} is paused")]
+    Paused { operation: PausableOperation },
+
+    #[error("All operations are paused")]
+    AllPaused {},
+
+    #[error("No pending ownership transfer")]
+    NoPendingOwner {},
+
+    #[error("Ownership transfer expired")]
+    OwnershipTransferExpired {},
+
+    #[error("Max members reached")]
+    MaxMembersReached {},
+
     #[error("Max whitelisted tokens reached")]
     MaxWhitelistedTokensReached {},
 
@@ -84,12 +113,27 @@ pub enum ContractError {
     #[error("Proposal not found")]
     ProposalNotFound {},
 
+    #[error("Proposal does not belong to cooperative")]
+    ProposalNotInCooperative {},
+
     #[error("Proposal already executed")]
     ProposalAlreadyExecuted {},
 
+    #[error("Proposal cancelled")]
+    ProposalCancelled {},
+
+    #[error("Invalid proposal deposit")]
+    InvalidProposalDeposit {},
+
+    #[error("No proposal deposit to settle")]
+    NoProposalDeposit {},
+
     #[error("No weight to withdraw")]
     NoWeightsToWithdraw {},
 
+    #[error("Vote weight locked until {unlock_at}")]
+    VoteLocked { unlock_at: u64 },
+
     #[error("No reward available")]
     NoRewardsAvailable {},
 
@@ -99,6 +143,9 @@ pub enum ContractError {
     #[error("Insufficient pool funds")]
     InsufficientPoolFunds {},
 
+    #[error("Queued withdrawal not found")]
+    WithdrawalNotFound {},
+
     #[error("Feature not implemented")]
     NotImplemented {},
 }
@@ -1,5 +1,7 @@
+pub mod asset;
 pub mod contract;
 mod error;
+mod migrations;
 pub mod msg;
 pub mod state;
 
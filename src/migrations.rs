@@ -0,0 +1,535 @@
+use cosmwasm_std::{Env, QuerierWrapper, Storage};
+use semver::Version;
+
+use crate::ContractError;
+
+// Runs every storage migration newer than the stored contract version, in order
+pub fn migrate_storage(
+    storage: &mut dyn Storage,
+    querier: &QuerierWrapper,
+    env: &Env,
+    from: &Version,
+) -> Result<(), ContractError> {
+    if *from < Version::new(0, 2, 0) {
+        v0_2_0::migrate(storage, querier, env)?;
+    }
+
+    Ok(())
+}
+
+mod v0_2_0 {
+    use std::collections::HashMap;
+
+    use cosmwasm_std::{
+        Addr, Decimal, Decimal256, Env, Order, QuerierWrapper, StdResult, Storage, Uint128,
+    };
+    use cw_storage_plus::{Item, Map};
+    use serde::{Deserialize, Serialize};
+
+    use crate::asset::AssetInfo;
+    use crate::contract::execute::register_token;
+    use crate::state::{
+        Conviction, Cooperative, CooperativeRewardsPool, CorporativeName, GovernanceParams, Loan,
+        LoanStatus, Member, Price, Proposal, ProposalData, ProposalId, ProposalOutcome,
+        ProposalType, RiskProfile, State, Vote, WhitelistedToken, WhitelistedTokenId, COOPERATIVES,
+        COOPERATIVE_MEMBERS, LOANS, PRICES, PROPOSALS, REWARDS_POOLS, STATE, TOKENS, VOTES,
+    };
+    use crate::ContractError;
+
+    #[derive(Serialize, Deserialize)]
+    struct LegacyState {
+        owner: Addr,
+        weight_token: String,
+        total_corporatives: u64,
+        total_pooled_funds: Vec<(WhitelistedTokenId, Uint128)>,
+        current_proposal_id: u64,
+        current_whitelisted_token_id: u64,
+        current_loan_id: u64,
+    }
+
+    // Cooperative layout with members (and their loans) embedded in the record
+    #[derive(Serialize, Deserialize)]
+    struct LegacyCooperative {
+        name: CorporativeName,
+        total_funds: Vec<(WhitelistedTokenId, Uint128)>,
+        members: Vec<LegacyMember>,
+        risk_profile: RiskProfile,
+        whitelisted_tokens: Vec<LegacyWhitelistedToken>,
+    }
+
+    // Whitelisted token layout without decimals and symbol
+    #[derive(Serialize, Deserialize)]
+    struct LegacyWhitelistedToken {
+        denom: String,
+        contract_addr: Option<Addr>,
+        is_native: bool,
+        max_loan_ratio: Decimal,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct LegacyMember {
+        address: Addr,
+        contribution: Vec<(WhitelistedTokenId, Uint128)>,
+        share: Vec<(WhitelistedTokenId, Uint128)>,
+        joined_at: u64,
+        reputation_score: Decimal,
+        loans: Vec<LegacyLoan>,
+    }
+
+    // Loan layout without cooperative and borrower, embedded in its member
+    #[derive(Serialize, Deserialize)]
+    struct LegacyLoan {
+        id: u64,
+        amount: Uint128,
+        token: Addr,
+        collaterals: Vec<Addr>,
+        collaterals_amount: Vec<Uint128>,
+        interest_rate: Decimal,
+        status: LoanStatus,
+    }
+
+    // Rewards pool layout without the reward-per-share index
+    #[derive(Serialize, Deserialize)]
+    struct LegacyRewardsPool {
+        cooperative_name: String,
+        token_id: WhitelistedTokenId,
+        total_rewards: Uint128,
+        distributed_rewards: Uint128,
+    }
+
+    // Proposal layout with votes embedded in the record
+    #[derive(Serialize, Deserialize)]
+    struct LegacyProposal {
+        id: ProposalId,
+        description: String,
+        data: ProposalData,
+        votes: Vec<LegacyVote>,
+        aye_count: u64,
+        nay_count: u64,
+        aye_weights: u64,
+        nay_weights: u64,
+        end_time: u64,
+        quorum: Option<Decimal>,
+        proposal_type: ProposalType,
+        outcome: Option<ProposalOutcome>,
+        executed: bool,
+    }
+
+    // Vote layout where `conviction` holds the raw weight
+    #[derive(Serialize, Deserialize)]
+    struct LegacyVote {
+        voter: Addr,
+        conviction: Uint128,
+        voted_at: u64,
+    }
+
+    // Limits that were hard-coded before they became configurable
+    const LEGACY_MAX_MEMBERS: u64 = 20;
+    const LEGACY_MAX_TOKENS: u64 = 5;
+
+    // Decimals of the Cosmos SDK native coins the cooperatives were launched with
+    const LEGACY_DECIMALS: u8 = 6;
+
+    // one week voting period, one day conviction lock period
+    const DEFAULT_VOTING_PERIOD: u64 = 604_800;
+    const DEFAULT_VOTE_LOCK_PERIOD: u64 = 86_400;
+
+    const LEGACY_STATE: Item<LegacyState> = Item::new("state");
+
+    const LEGACY_COOPERATIVES: Map<CorporativeName, LegacyCooperative> = Map::new("cooperatives");
+
+    const LEGACY_PROPOSALS: Map<ProposalId, LegacyProposal> = Map::new("proposals");
+    // Replaced by the cooperative index on proposals
+    const LEGACY_COOPERATIVES_PROPOSALS: Map<CorporativeName, Vec<ProposalId>> =
+        Map::new("cooperatives_proposals");
+
+    const LEGACY_REWARDS_POOLS: Map<(CorporativeName, WhitelistedTokenId), LegacyRewardsPool> =
+        Map::new("rewards_pools");
+
+    pub fn migrate(
+        storage: &mut dyn Storage,
+        querier: &QuerierWrapper,
+        env: &Env,
+    ) -> Result<(), ContractError> {
+        migrate_state(storage)?;
+        migrate_cooperatives(storage, querier)?;
+        migrate_proposals(storage, env)?;
+        migrate_tokens(storage)?;
+        migrate_pool_shares(storage)?;
+
+        Ok(())
+    }
+
+    fn migrate_state(storage: &mut dyn Storage) -> StdResult<()> {
+        let legacy = LEGACY_STATE.load(storage)?;
+
+        let state = State {
+            owner: legacy.owner,
+            guardian: None,
+            weight_token: legacy.weight_token,
+            total_corporatives: legacy.total_corporatives,
+            total_pooled_funds: legacy.total_pooled_funds,
+            current_proposal_id: legacy.current_proposal_id,
+            current_whitelisted_token_id: legacy.current_whitelisted_token_id,
+            current_loan_id: legacy.current_loan_id,
+            current_withdrawal_id: 0,
+            protocol_fee: Decimal::zero(),
+            max_members_per_cooperative: LEGACY_MAX_MEMBERS,
+            max_tokens_per_cooperative: LEGACY_MAX_TOKENS,
+            governance: GovernanceParams {
+                quorum: Decimal::one(),
+                voting_period: DEFAULT_VOTING_PERIOD,
+                vote_lock_period: DEFAULT_VOTE_LOCK_PERIOD,
+            },
+        };
+        STATE.save(storage, &state)
+    }
+
+    // Moves members out of the cooperative record and their loans into the loan registry
+    fn migrate_cooperatives(
+        storage: &mut dyn Storage,
+        querier: &QuerierWrapper,
+    ) -> Result<(), ContractError> {
+        let cooperatives = LEGACY_COOPERATIVES
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (name, legacy) in cooperatives {
+            let member_count = legacy.members.len() as u64;
+
+            for member in legacy.members {
+                for loan in member.loans {
+                    let loan = Loan {
+                        id: loan.id,
+                        cooperative: name.clone(),
+                        borrower: member.address.clone(),
+                        amount: loan.amount,
+                        token: loan.token.to_string(),
+                        collaterals: loan
+                            .collaterals
+                            .iter()
+                            .map(|collateral| collateral.to_string())
+                            .collect(),
+                        collateral_shares: loan.collaterals_amount.clone(),
+                        collaterals_amount: loan.collaterals_amount,
+                        interest_rate: loan.interest_rate,
+                        status: loan.status,
+                    };
+                    LOANS.save(storage, loan.id, &loan)?;
+                }
+
+                COOPERATIVE_MEMBERS.save(
+                    storage,
+                    (name.clone(), member.address.clone()),
+                    &Member {
+                        address: member.address,
+                        contribution: member.contribution,
+                        share: member.share,
+                        joined_at: member.joined_at,
+                        reputation_score: member.reputation_score,
+                        auto_compound: false,
+                    },
+                )?;
+            }
+
+            let mut whitelisted_tokens = vec![];
+            for token in legacy.whitelisted_tokens {
+                let mut token = WhitelistedToken {
+                    symbol: token.denom.clone(),
+                    denom: token.denom,
+                    contract_addr: token.contract_addr,
+                    is_native: token.is_native,
+                    max_loan_ratio: token.max_loan_ratio,
+                    decimals: LEGACY_DECIMALS,
+                };
+
+                // CW20 tokens report their own decimals
+                let decimals = AssetInfo::from_whitelisted(&token)?
+                    .decimals(querier)
+                    .map_err(|e| ContractError::MigrationFailed {
+                        msg: format!(
+                            "decimals of token {} could not be queried: {e}",
+                            token.denom
+                        ),
+                    })?;
+                if let Some(decimals) = decimals {
+                    token.decimals = decimals;
+                }
+                whitelisted_tokens.push(token);
+            }
+
+            let cooperative = Cooperative {
+                name: legacy.name,
+                total_funds: legacy.total_funds,
+                total_shares: vec![],
+                total_borrowed: vec![],
+                queued_withdrawals: vec![],
+                member_count,
+                risk_profile: legacy.risk_profile,
+                whitelisted_tokens,
+                proposal_deposit: None,
+                frozen: false,
+            };
+            COOPERATIVES.save(storage, name, &cooperative)?;
+        }
+
+        Ok(())
+    }
+
+    // Moves embedded votes into vote receipts and records each proposal's cooperative
+    fn migrate_proposals(storage: &mut dyn Storage, env: &Env) -> Result<(), ContractError> {
+        let cooperative_proposals = LEGACY_COOPERATIVES_PROPOSALS
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut proposal_cooperatives: HashMap<ProposalId, CorporativeName> = HashMap::new();
+        for (cooperative_name, proposal_ids) in cooperative_proposals {
+            LEGACY_COOPERATIVES_PROPOSALS.remove(storage, cooperative_name.clone());
+            for proposal_id in proposal_ids {
+                proposal_cooperatives.insert(proposal_id, cooperative_name.clone());
+            }
+        }
+
+        let proposals = LEGACY_PROPOSALS
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (proposal_id, legacy) in proposals {
+            // Escrowed weights can only be withdrawn through the proposal's cooperative
+            let cooperative = proposal_cooperatives
+                .get(&proposal_id)
+                .cloned()
+                .ok_or_else(|| ContractError::MigrationFailed {
+                    msg: format!("proposal {proposal_id} is not listed in any cooperative"),
+                })?;
+
+            for vote in legacy.votes {
+                // Legacy votes did not record their direction
+                let vote = Vote {
+                    proposal_id,
+                    voter: vote.voter,
+                    aye: None,
+                    weight: vote.conviction,
+                    effective_weight: vote.conviction,
+                    conviction: Conviction::Locked1x,
+                    voted_at: vote.voted_at,
+                    locked_until: legacy.end_time,
+                    // Legacy withdrawals zeroed the weight
+                    withdrawn: vote.conviction.is_zero(),
+                };
+                VOTES.save(storage, (proposal_id, vote.voter.clone()), &vote)?;
+            }
+
+            // Legacy proposals did not record a proposer
+            let proposal = Proposal {
+                id: legacy.id,
+                cooperative,
+                proposer: env.contract.address.clone(),
+                description: legacy.description,
+                data: legacy.data,
+                aye_count: legacy.aye_count,
+                nay_count: legacy.nay_count,
+                aye_weights: Uint128::from(legacy.aye_weights),
+                nay_weights: Uint128::from(legacy.nay_weights),
+                end_time: legacy.end_time,
+                quorum: legacy.quorum,
+                proposal_type: legacy.proposal_type,
+                outcome: legacy.outcome,
+                executed: legacy.executed,
+                cancelled: false,
+                deposit: None,
+                deposit_settled: false,
+            };
+            // Indexing reads the stored record, so the legacy one goes first
+            LEGACY_PROPOSALS.remove(storage, proposal_id);
+            PROPOSALS.save(storage, proposal_id, &proposal)?;
+        }
+
+        Ok(())
+    }
+
+    // Rebuilds the token registry with one id per denom or contract, and rekeys the
+    // accounting that used a token's position in the cooperative whitelist as its id
+    fn migrate_tokens(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        // Legacy ids were shared by all tokens whitelisted together, and every native
+        // denom was registered as "NATIVE", so only CW20 prices can be carried over
+        let legacy_ids = TOKENS
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<(String, WhitelistedTokenId)>>>()?;
+        let legacy_prices = PRICES
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<(WhitelistedTokenId, Price)>>>()?;
+        for (key, _) in &legacy_ids {
+            TOKENS.remove(storage, key.clone());
+        }
+        for (id, _) in &legacy_prices {
+            PRICES.remove(storage, *id);
+        }
+
+        let mut state = STATE.load(storage)?;
+        state.current_whitelisted_token_id = 0;
+        state.total_pooled_funds = vec![];
+
+        let cooperatives = COOPERATIVES
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (name, mut cooperative) in cooperatives {
+            // Registry id of each whitelist position
+            let mut token_ids: Vec<WhitelistedTokenId> = vec![];
+            for token in &cooperative.whitelisted_tokens {
+                let token_id = register_token(storage, &mut state, token)?;
+                token_ids.push(token_id);
+
+                // Carry over the price recorded under the legacy id. The "NATIVE" price
+                // belonged to one denom, so native tokens are left to be priced again.
+                let legacy_key = match &token.contract_addr {
+                    Some(contract_addr) if !token.is_native => contract_addr.to_string(),
+                    _ => continue,
+                };
+                let legacy_price = legacy_ids
+                    .iter()
+                    .find(|(key, _)| *key == legacy_key)
+                    .and_then(|(_, legacy_id)| {
+                        legacy_prices.iter().find(|(id, _)| id == legacy_id)
+                    });
+                if let Some((_, price)) = legacy_price {
+                    PRICES.save(storage, token_id, price)?;
+                }
+            }
+
+            // Amounts under a key that is not a whitelist position cannot be attributed
+            // to a token, the migration fails rather than dropping them
+            let token_id_of = |idx: WhitelistedTokenId| {
+                token_ids
+                    .get(idx as usize)
+                    .copied()
+                    .ok_or_else(|| ContractError::MigrationFailed {
+                        msg: format!(
+                            "cooperative {name} has amounts under unknown token key {idx}"
+                        ),
+                    })
+            };
+            let rekey = |amounts: Vec<(WhitelistedTokenId, Uint128)>| {
+                amounts
+                    .into_iter()
+                    .map(|(idx, amount)| Ok((token_id_of(idx)?, amount)))
+                    .collect::<Result<Vec<_>, ContractError>>()
+            };
+
+            cooperative.total_funds = rekey(cooperative.total_funds)?;
+            for (token_id, amount) in &cooperative.total_funds {
+                match state
+                    .total_pooled_funds
+                    .iter_mut()
+                    .find(|(id, _)| id == token_id)
+                {
+                    Some(pooled) => pooled.1 += *amount,
+                    None => state.total_pooled_funds.push((*token_id, *amount)),
+                }
+            }
+            COOPERATIVES.save(storage, name.clone(), &cooperative)?;
+
+            let members = COOPERATIVE_MEMBERS
+                .prefix(name.clone())
+                .range(storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (address, mut member) in members {
+                member.contribution = rekey(member.contribution)?;
+                member.share = rekey(member.share)?;
+                COOPERATIVE_MEMBERS.save(storage, (name.clone(), address), &member)?;
+            }
+
+            // Undistributed rewards are left out of the index, the first update spreads them
+            // over the pool shares
+            let pools = LEGACY_REWARDS_POOLS
+                .prefix(name.clone())
+                .range(storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (idx, _) in &pools {
+                LEGACY_REWARDS_POOLS.remove(storage, (name.clone(), *idx));
+            }
+            for (idx, pool) in pools {
+                let token_id = token_id_of(idx)?;
+                let pool = CooperativeRewardsPool {
+                    cooperative_name: pool.cooperative_name,
+                    token_id,
+                    total_rewards: pool.total_rewards,
+                    distributed_rewards: pool.distributed_rewards,
+                    indexed_rewards: pool.distributed_rewards,
+                    reward_per_share: Decimal256::zero(),
+                };
+                REWARDS_POOLS.save(storage, (name.clone(), token_id), &pool)?;
+            }
+        }
+
+        STATE.save(storage, &state)?;
+
+        Ok(())
+    }
+
+    // Converts contributions into pool shares at an exchange rate of one. Collateral used to
+    // be taken out of the borrower's contribution, it now stays there as locked shares.
+    // Active loans are counted as borrowed from their pool.
+    fn migrate_pool_shares(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        let add = |amounts: &mut Vec<(WhitelistedTokenId, Uint128)>,
+                   token_id: WhitelistedTokenId,
+                   amount: Uint128| {
+            match amounts.iter_mut().find(|(id, _)| *id == token_id) {
+                Some(entry) => entry.1 += amount,
+                None => amounts.push((token_id, amount)),
+            }
+        };
+
+        let loans = LOANS
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, loan)| loan))
+            .collect::<StdResult<Vec<Loan>>>()?;
+        let active_loans = loans
+            .iter()
+            .filter(|loan| loan.status == LoanStatus::Active)
+            .collect::<Vec<_>>();
+
+        let cooperatives = COOPERATIVES
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for (name, mut cooperative) in cooperatives {
+            cooperative.total_shares = vec![];
+            cooperative.total_borrowed = vec![];
+            for loan in active_loans.iter().filter(|loan| loan.cooperative == name) {
+                if let Some(token_id) = TOKENS.may_load(storage, loan.token.clone())? {
+                    add(&mut cooperative.total_borrowed, token_id, loan.amount);
+                }
+            }
+
+            let members = COOPERATIVE_MEMBERS
+                .prefix(name.clone())
+                .range(storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+            for (address, mut member) in members {
+                let loans = active_loans
+                    .iter()
+                    .filter(|loan| loan.cooperative == name && loan.borrower == address);
+                for loan in loans {
+                    for (collateral, amount) in
+                        loan.collaterals.iter().zip(&loan.collaterals_amount)
+                    {
+                        if let Some(token_id) = TOKENS.may_load(storage, collateral.clone())? {
+                            add(&mut member.contribution, token_id, *amount);
+                        }
+                    }
+                }
+
+                member.share = member.contribution.clone();
+                for (token_id, shares) in &member.share {
+                    add(&mut cooperative.total_shares, *token_id, *shares);
+                }
+                COOPERATIVE_MEMBERS.save(storage, (name.clone(), address), &member)?;
+            }
+
+            COOPERATIVES.save(storage, name, &cooperative)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,18 +1,33 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
 use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
 
 use crate::state::{
-    Cooperative, CorporativeName, Loan, Member, Proposal, RiskProfile, WhitelistedToken,
-    WhitelistedTokenId,
+    Conviction, Cooperative, CorporativeName, GovernanceParams, Loan, LoanStatus, Member,
+    PausableOperation, PendingOwner, Proposal, ProposalData, ProposalDeposit, ProposalId,
+    ProposalOutcome, ProposalStatus, ProposalType, QueuedWithdrawal, RegisteredToken, RiskProfile,
+    Vote, WhitelistedToken, WhitelistedTokenId,
 };
 
 #[cw_serde]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    pub owner: Option<String>, // defaults to the instantiating address
+    pub guardian: Option<String>,
+    pub weight_token: String, // native denom escrowed as voting weight
+    pub protocol_fee: Decimal,
+    pub max_members_per_cooperative: u64,
+    pub max_tokens_per_cooperative: u64,
+    pub governance: GovernanceParams,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {}
 
 #[cw_serde]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     UpdateTokenPrice {
-        token_addr: Addr,
+        token_addr: String, // denom for native tokens, contract address for CW20 tokens
         usd_price: Decimal,
     },
     CreateCooperative {
@@ -20,6 +35,7 @@ pub enum ExecuteMsg {
         risk_profile: RiskProfile,
         initial_members: Vec<Member>,
         initial_whitelisted_tokens: Vec<WhitelistedToken>,
+        proposal_deposit: Option<ProposalDeposit>,
     },
     FundCooperative {
         cooperative_name: CorporativeName,
@@ -27,44 +43,172 @@ pub enum ExecuteMsg {
         is_native: bool,
         amount: Uint128,
     },
+    // Tokens are referenced by denom for native tokens, by contract address for CW20 tokens
     Borrow {
         cooperative_name: CorporativeName,
-        tokens_in: Vec<Addr>,
+        tokens_in: Vec<String>,
         amount_in: Vec<Uint128>,
-        token_out: Addr,
+        token_out: String,
         min_amount_out: Uint128,
     },
     Repay {
         cooperative_name: CorporativeName,
-        token: Addr,
+        token: String,
     },
     Propose {
         cooperative_name: CorporativeName,
-        proposal: Proposal,
+        proposal: ProposalMsg,
     },
     Vote {
         cooperative_name: CorporativeName,
         proposal_id: u64,
         weight: Uint128,
         aye: bool,
+        conviction: Conviction,
     },
     WithdrawWeight {
         cooperative_name: CorporativeName,
         proposal_id: u64,
     },
+    // Withdraws everything not locked as collateral unless an amount is given, or only the
+    // interest earned with `rewards_only`
     WithdrawContributionAndReward {
         cooperative_name: CorporativeName,
-        token: Addr,
+        token: String,
+        amount: Option<Uint128>,
+        rewards_only: Option<bool>,
+    },
+    // Pays the rewards earned by the member's pool shares of a token, keeping the shares
+    ClaimRewards {
+        cooperative_name: CorporativeName,
+        token: String,
+    },
+    // Reinvests the member's rewards as contribution instead of paying them out. Compounding
+    // is lazy: rewards are reinvested when the member's rewards are next settled (fund,
+    // withdraw, claim, cancel withdrawal), at the exchange rate of that moment.
+    SetAutoCompound {
+        cooperative_name: CorporativeName,
+        enabled: bool,
+    },
+    // Takes a queued withdrawal out of the queue, restoring the shares and principal of its
+    // unpaid amount
+    CancelWithdrawal {
+        cooperative_name: CorporativeName,
+        token: String,
+        withdrawal_id: u64,
     },
     ExecuteProposal {
         cooperative_name: CorporativeName,
         proposal_id: u64,
     },
+    SettleProposalDeposit {
+        cooperative_name: CorporativeName,
+        proposal_id: u64,
+    },
+    CancelProposal {
+        cooperative_name: CorporativeName,
+        proposal_id: u64,
+    },
+    // Sends protocol fees of a token to the recipient, all of them unless an amount is given.
+    // Defaults to the owner as recipient.
+    WithdrawProtocolFees {
+        token: String,
+        amount: Option<Uint128>,
+        recipient: Option<String>,
+    },
+    UpdateConfig {
+        protocol_fee: Option<Decimal>,
+        max_members_per_cooperative: Option<u64>,
+        max_tokens_per_cooperative: Option<u64>,
+        governance: Option<GovernanceParams>,
+    },
+    ProposeNewOwner {
+        new_owner: String,
+        expires_in: u64, // seconds the new owner has to accept
+    },
+    AcceptOwnership {},
+    CancelOwnershipTransfer {},
+    SetGuardian {
+        guardian: Option<String>,
+    },
+    // Pauses operations of a cooperative, or of every cooperative when no name is given.
+    // All operations are paused when none are listed.
+    Pause {
+        cooperative_name: Option<CorporativeName>,
+        operations: Option<Vec<PausableOperation>>,
+    },
+    Unpause {
+        cooperative_name: Option<CorporativeName>,
+        operations: Option<Vec<PausableOperation>>,
+    },
+    Receive(Cw20ReceiveMsg),
+}
+
+// Proposal as submitted by a member; the contract fills in the rest
+#[cw_serde]
+pub struct ProposalMsg {
+    pub description: String,
+    pub data: ProposalData,
+    pub end_time: u64,           // 0 uses the default voting period
+    pub quorum: Option<Decimal>, // defaults to the governance quorum
+    pub proposal_type: ProposalType,
+}
+
+// Hook messages embedded in a CW20 `Send` to this contract
+#[cw_serde]
+pub enum Cw20HookMsg {
+    Fund {
+        cooperative_name: CorporativeName,
+    },
+    Repay {
+        cooperative_name: CorporativeName,
+        loan_id: u64,
+    },
+    // Adds the tokens to the cooperative's rewards pool
+    Contribute {
+        cooperative_name: CorporativeName,
+    },
+}
+
+// Messages chain governance can send through the sudo entry point
+#[cw_serde]
+pub enum SudoMsg {
+    Pause {
+        cooperative_name: Option<CorporativeName>,
+        operations: Option<Vec<PausableOperation>>,
+    },
+    Unpause {
+        cooperative_name: Option<CorporativeName>,
+        operations: Option<Vec<PausableOperation>>,
+    },
+    ForceUpdatePrices {
+        prices: Vec<(String, Decimal)>,
+    },
+    SetOwner {
+        owner: String,
+    },
+    FreezeCooperative {
+        cooperative_name: CorporativeName,
+    },
+    UnfreezeCooperative {
+        cooperative_name: CorporativeName,
+    },
 }
 
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
+    #[returns(GetConfigResponse)]
+    Config {},
+
+    #[returns(GetProtocolFeesResponse)]
+    ProtocolFees {},
+
+    #[returns(GetPauseStatusResponse)]
+    PauseStatus {
+        cooperative_name: Option<CorporativeName>,
+    },
+
     #[returns(GetCooperativeResponse)]
     GetCooperative { cooperative_name: CorporativeName },
 
@@ -80,17 +224,133 @@ pub enum QueryMsg {
         member_address: Addr,
     },
 
+    #[returns(GetListMembersResponse)]
+    ListMembers {
+        cooperative_name: CorporativeName,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetLoanResponse)]
+    GetLoan { loan_id: u64 },
+
+    #[returns(GetListLoansResponse)]
+    ListLoans {
+        cooperative_name: Option<CorporativeName>,
+        status: Option<LoanStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetListLoansResponse)]
+    ListLoansByBorrower {
+        borrower: Addr,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetListLoansResponse)]
+    ListLoansByToken {
+        token: String,
+        status: Option<LoanStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
     #[returns(GetListCooperativesResponse)]
     ListCooperatives {},
 
     #[returns(GetProposalResponse)]
     GetProposal { proposal_id: u64 },
 
+    #[returns(GetListProposalsResponse)]
+    ListProposals {
+        cooperative_name: CorporativeName,
+        status: Option<ProposalStatus>,
+        start_after: Option<ProposalId>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetVoteResponse)]
+    GetVote {
+        proposal_id: ProposalId,
+        voter: Addr,
+    },
+
+    #[returns(GetListVotesResponse)]
+    ListVotesByVoter {
+        voter: Addr,
+        start_after: Option<ProposalId>,
+        limit: Option<u32>,
+    },
+
     #[returns(GetWhitelistedTokensResponse)]
     GetWhitelistedTokens { cooperative_name: CorporativeName },
 
     #[returns(GetTokenIdResponse)]
     GetTokenId { token: String },
+
+    #[returns(GetListTokensResponse)]
+    ListTokens {
+        start_after: Option<WhitelistedTokenId>,
+        limit: Option<u32>,
+    },
+
+    // Lending pool of a cooperative for a native denom or CW20 address
+    #[returns(GetPoolResponse)]
+    Pool {
+        cooperative_name: CorporativeName,
+        token: String,
+    },
+
+    #[returns(GetPendingRewardsResponse)]
+    PendingRewards {
+        cooperative_name: CorporativeName,
+        member: Addr,
+        token: String,
+    },
+
+    // Withdrawals waiting for liquidity in a pool, in the order they are filled
+    #[returns(GetWithdrawalQueueResponse)]
+    WithdrawalQueue {
+        cooperative_name: CorporativeName,
+        token: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetWithdrawalPositionResponse)]
+    WithdrawalPosition {
+        cooperative_name: CorporativeName,
+        token: String,
+        withdrawal_id: u64,
+    },
+}
+
+#[cw_serde]
+pub struct GetConfigResponse {
+    pub owner: Addr,
+    pub guardian: Option<Addr>,
+    pub weight_token: String,
+    pub protocol_fee: Decimal,
+    pub max_members_per_cooperative: u64,
+    pub max_tokens_per_cooperative: u64,
+    pub governance: GovernanceParams,
+    pub pending_owner: Option<PendingOwner>,
+}
+
+#[cw_serde]
+pub struct GetProtocolFeesResponse {
+    pub protocol_fee: Decimal,
+    pub fees: Vec<(WhitelistedTokenId, Uint128)>, // Withdrawable by the owner
+}
+
+#[cw_serde]
+pub struct GetPauseStatusResponse {
+    pub global: Vec<PausableOperation>,
+    pub cooperative: Vec<PausableOperation>,
+    pub sudo_global: Vec<PausableOperation>, // Paused by chain governance
+    pub sudo_cooperative: Vec<PausableOperation>,
 }
 
 #[cw_serde]
@@ -101,6 +361,22 @@ pub struct GetCooperativeResponse {
 #[cw_serde]
 pub struct GetMemberInfoResponse {
     pub info: Member,
+    pub loans: Vec<Loan>,
+}
+
+#[cw_serde]
+pub struct GetListMembersResponse {
+    pub members: Vec<Member>,
+}
+
+#[cw_serde]
+pub struct GetLoanResponse {
+    pub loan: Loan,
+}
+
+#[cw_serde]
+pub struct GetListLoansResponse {
+    pub loans: Vec<Loan>,
 }
 
 #[cw_serde]
@@ -113,6 +389,36 @@ pub struct GetProposalResponse {
     pub proposal: Proposal,
 }
 
+#[cw_serde]
+pub struct GetListProposalsResponse {
+    pub proposals: Vec<ProposalSummary>,
+}
+
+#[cw_serde]
+pub struct ProposalSummary {
+    pub id: ProposalId,
+    pub description: String,
+    pub proposal_type: ProposalType,
+    pub status: ProposalStatus,
+    pub outcome: Option<ProposalOutcome>,
+    pub aye_count: u64,
+    pub nay_count: u64,
+    pub aye_weights: Uint128,
+    pub nay_weights: Uint128,
+    pub end_time: u64,
+    pub executed: bool,
+}
+
+#[cw_serde]
+pub struct GetVoteResponse {
+    pub vote: Option<Vote>,
+}
+
+#[cw_serde]
+pub struct GetListVotesResponse {
+    pub votes: Vec<Vote>,
+}
+
 #[cw_serde]
 pub struct GetWhitelistedTokensResponse {
     pub tokens: Vec<WhitelistedToken>,
@@ -123,6 +429,43 @@ pub struct GetTokenIdResponse {
     pub token_id: WhitelistedTokenId,
 }
 
+#[cw_serde]
+pub struct GetListTokensResponse {
+    pub tokens: Vec<RegisteredToken>,
+}
+
+#[cw_serde]
+pub struct GetPoolResponse {
+    pub token_id: WhitelistedTokenId,
+    pub total_funds: Uint128,
+    pub total_shares: Uint128,
+    pub exchange_rate: Decimal, // Funds redeemed per pool share
+    pub total_borrowed: Uint128,
+    pub queued_withdrawals: Uint128,
+}
+
+#[cw_serde]
+pub struct GetPendingRewardsResponse {
+    pub token_id: WhitelistedTokenId,
+    pub pending_rewards: Uint128,
+    pub last_withdrawn_rewards: Uint128,
+    pub compounding_rewards: Uint128, // Reinvested at the next settlement of an auto-compounding member
+    pub compounding_shares: Uint128,  // Pool shares the reinvested rewards would mint now
+}
+
+#[cw_serde]
+pub struct GetWithdrawalQueueResponse {
+    pub total_queued: Uint128,
+    pub withdrawals: Vec<QueuedWithdrawal>,
+}
+
+#[cw_serde]
+pub struct GetWithdrawalPositionResponse {
+    pub withdrawal: QueuedWithdrawal,
+    pub position: u64,         // Withdrawals ahead in the queue
+    pub amount_ahead: Uint128, // Owed to the withdrawals ahead
+}
+
 // Member contribution and share response type
 #[cw_serde]
 pub struct MemberContributionAndShareResponse {
@@ -136,10 +479,14 @@ pub struct MemberContributionAndShareResponse {
     /// Contains (token_id, amount) pairs
     pub contributions: Vec<TokenAmount>,
 
-    /// The member's shares in the cooperative
+    /// The member's pool shares in the cooperative
     /// Contains (token_id, amount) pairs
     pub shares: Vec<TokenAmount>,
 
+    /// The funds the member's pool shares redeem for, contribution plus earned interest
+    /// Contains (token_id, amount) pairs
+    pub balances: Vec<TokenAmount>,
+
     /// The active loans of the member
     pub loans: Vec<Loan>,
 
@@ -2,8 +2,8 @@ use std::fmt;
 
 use schemars::JsonSchema;
 
-use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Decimal, Decimal256, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use serde::{Deserialize, Serialize};
 
 pub type WhitelistedTokenId = u64;
@@ -14,12 +14,60 @@ pub type ProposalId = u64;
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {
     pub owner: Addr,
+    pub guardian: Option<Addr>, // May pause operations during an incident
     pub weight_token: String,
     pub total_corporatives: u64,
     pub total_pooled_funds: Vec<(WhitelistedTokenId, Uint128)>,
     pub current_proposal_id: u64,
     pub current_whitelisted_token_id: u64,
     pub current_loan_id: u64,
+    pub current_withdrawal_id: u64,
+    pub protocol_fee: Decimal, // Share of loan interest kept by the protocol
+    pub max_members_per_cooperative: u64,
+    pub max_tokens_per_cooperative: u64,
+    pub governance: GovernanceParams,
+}
+
+// Defaults applied to proposals and votes across all cooperatives
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GovernanceParams {
+    pub quorum: Decimal,       // Used when a proposal does not set its own quorum
+    pub voting_period: u64,    // Used when a proposal does not set its own end time, in seconds
+    pub vote_lock_period: u64, // Base conviction lock period in seconds
+}
+
+// Operations that can be paused globally or per cooperative
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PausableOperation {
+    Fund,
+    Borrow,
+    Repay,
+    Withdraw,
+    Vote,
+}
+
+impl PausableOperation {
+    pub const ALL: [PausableOperation; 5] = [
+        PausableOperation::Fund,
+        PausableOperation::Borrow,
+        PausableOperation::Repay,
+        PausableOperation::Withdraw,
+        PausableOperation::Vote,
+    ];
+}
+
+impl fmt::Display for PausableOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// Ownership transfer awaiting acceptance by the new owner
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingOwner {
+    pub new_owner: Addr,
+    pub expires_at: u64,
 }
 
 // Whitelist token structure
@@ -29,27 +77,53 @@ pub struct WhitelistedToken {
     pub contract_addr: Option<Addr>,
     pub is_native: bool,
     pub max_loan_ratio: Decimal,
+    pub decimals: u8,
+    pub symbol: String, // human readable symbol, e.g. USDC for an `ibc/...` denom
+}
+
+// Protocol-level token record, shared by every cooperative whitelisting the token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RegisteredToken {
+    pub id: WhitelistedTokenId,
+    pub denom: String,
+    pub contract_addr: Option<Addr>,
+    pub is_native: bool,
+    pub decimals: u8,
+    pub symbol: String,
 }
 
 // Corporative data
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Cooperative {
     pub name: CorporativeName,
-    pub total_funds: Vec<(WhitelistedTokenId, Uint128)>,
-    pub members: Vec<Member>,
+    pub total_funds: Vec<(WhitelistedTokenId, Uint128)>, // Including lent out funds and interest
+    pub total_shares: Vec<(WhitelistedTokenId, Uint128)>, // Pool shares redeemable for total_funds
+    pub total_borrowed: Vec<(WhitelistedTokenId, Uint128)>, // Principal of active loans
+    pub queued_withdrawals: Vec<(WhitelistedTokenId, Uint128)>, // Owed to the withdrawal queue
+    pub member_count: u64,
     pub risk_profile: RiskProfile,
     pub whitelisted_tokens: Vec<WhitelistedToken>,
+    pub proposal_deposit: Option<ProposalDeposit>,
+    pub frozen: bool, // Set by chain governance, blocks every member operation
+}
+
+// Deposit escrowed when opening a proposal, must be a whitelisted token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ProposalDeposit {
+    pub token: String, // denom for native tokens, contract address for CW20 tokens
+    pub is_native: bool,
+    pub amount: Uint128,
 }
 
 // Cooperative member data
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Member {
     pub address: Addr,
-    pub contribution: Vec<(WhitelistedTokenId, Uint128)>,
-    pub share: Vec<(WhitelistedTokenId, Uint128)>,
+    pub contribution: Vec<(WhitelistedTokenId, Uint128)>, // Principal deposited per token
+    pub share: Vec<(WhitelistedTokenId, Uint128)>,        // Pool shares held per token
     pub joined_at: u64,
     pub reputation_score: Decimal,
-    pub loans: Vec<Loan>, // User loans
+    pub auto_compound: bool, // Reinvests earned rewards as contribution instead of paying them
 }
 
 // Cooperative risk profile
@@ -62,14 +136,28 @@ pub struct RiskProfile {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Loan {
     pub id: u64,
+    pub cooperative: CorporativeName,
+    pub borrower: Addr,
     pub amount: Uint128,
-    pub token: Addr,
-    pub collaterals: Vec<Addr>,
+    pub token: String, // denom for native tokens, contract address for CW20 tokens
+    pub collaterals: Vec<String>,
     pub collaterals_amount: Vec<Uint128>,
+    pub collateral_shares: Vec<Uint128>, // Borrower pool shares locked for each collateral
     pub interest_rate: Decimal,
     pub status: LoanStatus,
 }
 
+impl Loan {
+    pub fn interest(&self) -> Uint128 {
+        self.amount.mul_ceil(self.interest_rate)
+    }
+
+    // Principal plus interest owed on repayment
+    pub fn amount_due(&self) -> Uint128 {
+        self.amount + self.interest()
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub enum LoanStatus {
     Active,
@@ -77,6 +165,12 @@ pub enum LoanStatus {
     Defaulted,
 }
 
+impl fmt::Display for LoanStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
 // Externally controlled liquidity
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct LiquidityPosition {
@@ -89,25 +183,93 @@ pub struct LiquidityPosition {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Proposal {
     pub id: ProposalId,
+    pub cooperative: CorporativeName,
+    pub proposer: Addr,
     pub description: String,
     pub data: ProposalData,
-    pub votes: Vec<Vote>,
     pub aye_count: u64,
     pub nay_count: u64,
-    pub aye_weights: u64,
-    pub nay_weights: u64,
+    pub aye_weights: Uint128,
+    pub nay_weights: Uint128,
     pub end_time: u64,
     pub quorum: Option<Decimal>,
     pub proposal_type: ProposalType,
     pub outcome: Option<ProposalOutcome>,
     pub executed: bool,
+    pub cancelled: bool,
+    pub deposit: Option<ProposalDeposit>,
+    pub deposit_settled: bool,
+}
+
+impl Proposal {
+    pub fn status(&self, now: u64) -> ProposalStatus {
+        if self.cancelled {
+            return ProposalStatus::Cancelled;
+        }
+
+        if self.executed {
+            return ProposalStatus::Executed;
+        }
+
+        match self.outcome {
+            Some(ProposalOutcome::Passed) => ProposalStatus::Passed,
+            Some(ProposalOutcome::Rejected) => ProposalStatus::Rejected,
+            None if now >= self.end_time => ProposalStatus::Expired,
+            None => ProposalStatus::Active,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Vote {
+    pub proposal_id: ProposalId,
     pub voter: Addr,
-    pub conviction: Uint128,
+    pub aye: Option<bool>, // Unknown for votes migrated from before directions were recorded
+    pub weight: Uint128,   // Escrowed weight token amount
+    pub effective_weight: Uint128, // Weight counted after the conviction multiplier
+    pub conviction: Conviction,
     pub voted_at: u64,
+    pub locked_until: u64,
+    pub withdrawn: bool, // Escrowed weight returned to the voter
+}
+
+// Conviction multiplies a vote's weight in exchange for locking the escrowed
+// weight tokens for a number of lock periods after the proposal ends
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum Conviction {
+    None,
+    Locked1x,
+    Locked2x,
+    Locked3x,
+    Locked4x,
+    Locked5x,
+    Locked6x,
+}
+
+impl Conviction {
+    pub fn multiplier(&self) -> Decimal {
+        match self {
+            Conviction::None => Decimal::permille(100),
+            Conviction::Locked1x => Decimal::one(),
+            Conviction::Locked2x => Decimal::percent(200),
+            Conviction::Locked3x => Decimal::percent(300),
+            Conviction::Locked4x => Decimal::percent(400),
+            Conviction::Locked5x => Decimal::percent(500),
+            Conviction::Locked6x => Decimal::percent(600),
+        }
+    }
+
+    pub fn lock_periods(&self) -> u64 {
+        match self {
+            Conviction::None => 0,
+            Conviction::Locked1x => 1,
+            Conviction::Locked2x => 2,
+            Conviction::Locked3x => 4,
+            Conviction::Locked4x => 8,
+            Conviction::Locked5x => 16,
+            Conviction::Locked6x => 32,
+        }
+    }
 }
 
 // Whitelist token structure
@@ -118,9 +280,14 @@ pub struct ProposalData {
     pub token_addr: Option<Addr>,
     pub is_native: Option<bool>,
     pub max_loan_ratio: Option<Decimal>,
+    pub decimals: Option<u8>,
+    pub symbol: Option<String>,
 
     // add member data
     pub new_member_addr: Option<Addr>,
+
+    // cancel proposal data
+    pub proposal_id: Option<ProposalId>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -130,6 +297,7 @@ pub enum ProposalType {
     AddLP,
     ApproveLoan,
     LiquidateCollateral,
+    CancelProposal,
 }
 
 impl fmt::Display for ProposalType {
@@ -144,6 +312,17 @@ pub enum ProposalOutcome {
     Rejected,
 }
 
+// Lifecycle status derived from a proposal's outcome and end time
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum ProposalStatus {
+    Active,
+    Passed,
+    Rejected,
+    Executed,
+    Expired, // ended without reaching quorum
+    Cancelled,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Price {
     pub latest_price_to_usd: Decimal,
@@ -156,7 +335,22 @@ pub struct CooperativeRewardsPool {
     pub cooperative_name: String,
     pub token_id: WhitelistedTokenId,
     pub total_rewards: Uint128,
-    pub distributed_rewards: Uint128,
+    pub distributed_rewards: Uint128, // Paid out to members
+    pub indexed_rewards: Uint128,     // Already spread over the pool shares by the index
+    pub reward_per_share: Decimal256, // Rewards earned per pool share of the token
+}
+
+// Withdrawal waiting for pool liquidity, the member's shares are already burned
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct QueuedWithdrawal {
+    pub id: u64,
+    pub cooperative_name: CorporativeName,
+    pub token_id: WhitelistedTokenId,
+    pub member: Addr,
+    pub amount: Uint128,    // Still owed to the member
+    pub shares: Uint128,    // Burned for the amount still owed
+    pub principal: Uint128, // Contribution withdrawn with those shares
+    pub queued_at: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -164,23 +358,133 @@ pub struct MemberRewards {
     pub cooperative_name: String,
     pub member_address: Addr,
     pub token_id: WhitelistedTokenId,
-    pub last_withdrawn_rewards: Uint128,
+    pub last_withdrawn_rewards: Uint128, // Paid by the member's last claim
+    pub reward_per_share: Decimal256,    // Pool index when the member's rewards were last settled
+    pub pending_rewards: Uint128,        // Earned and not yet claimed
 }
 
 pub const STATE: Item<State> = Item::new("state");
 
-pub const TOKENS: Map<Addr, WhitelistedTokenId> = Map::new("tokens");
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+
+pub const PAUSED: Item<Vec<PausableOperation>> = Item::new("paused");
+
+pub const COOPERATIVE_PAUSED: Map<CorporativeName, Vec<PausableOperation>> =
+    Map::new("cooperative_paused");
+
+// Pauses set by chain governance, which only a sudo unpause can lift
+pub const SUDO_PAUSED: Item<Vec<PausableOperation>> = Item::new("sudo_paused");
+
+pub const SUDO_COOPERATIVE_PAUSED: Map<CorporativeName, Vec<PausableOperation>> =
+    Map::new("sudo_cooperative_paused");
+
+// Token id by denom for native tokens, by contract address for CW20 tokens
+pub const TOKENS: Map<String, WhitelistedTokenId> = Map::new("tokens");
+
+pub const TOKEN_REGISTRY: Map<WhitelistedTokenId, RegisteredToken> = Map::new("token_registry");
 
 pub const PRICES: Map<WhitelistedTokenId, Price> = Map::new("prices");
 
+// Protocol fees taken from loan interest per token, held apart from cooperative funds
+pub const PROTOCOL_TREASURY: Map<WhitelistedTokenId, Uint128> = Map::new("protocol_treasury");
+
 pub const COOPERATIVES: Map<CorporativeName, Cooperative> = Map::new("cooperatives");
 
 pub const MEMBERS: Map<Addr, Vec<CorporativeName>> = Map::new("members");
 
-pub const PROPOSALS: Map<ProposalId, Proposal> = Map::new("proposals");
+pub const COOPERATIVE_MEMBERS: Map<(CorporativeName, Addr), Member> =
+    Map::new("cooperative_members");
+
+// Loans are indexed by cooperative, borrower, borrowed token, status and
+// cooperative with status
+pub struct LoanIndexes<'a> {
+    pub cooperative: MultiIndex<'a, CorporativeName, Loan, u64>,
+    pub borrower: MultiIndex<'a, Addr, Loan, u64>,
+    pub token: MultiIndex<'a, String, Loan, u64>,
+    pub status: MultiIndex<'a, String, Loan, u64>,
+    pub cooperative_status: MultiIndex<'a, (CorporativeName, String), Loan, u64>,
+}
 
-pub const COOPERATIVES_PROPOSALS: Map<CorporativeName, Vec<ProposalId>> =
-    Map::new("cooperatives_proposals");
+impl IndexList<Loan> for LoanIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Loan>> + '_> {
+        let v: Vec<&dyn Index<Loan>> = vec![
+            &self.cooperative,
+            &self.borrower,
+            &self.token,
+            &self.status,
+            &self.cooperative_status,
+        ];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const LOANS: IndexedMap<u64, Loan, LoanIndexes> = IndexedMap::new(
+    "loans",
+    LoanIndexes {
+        cooperative: MultiIndex::new(
+            |_pk, l| l.cooperative.clone(),
+            "loans",
+            "loans__cooperative",
+        ),
+        borrower: MultiIndex::new(|_pk, l| l.borrower.clone(), "loans", "loans__borrower"),
+        token: MultiIndex::new(|_pk, l| l.token.clone(), "loans", "loans__token"),
+        status: MultiIndex::new(|_pk, l| l.status.to_string(), "loans", "loans__status"),
+        cooperative_status: MultiIndex::new(
+            |_pk, l| (l.cooperative.clone(), l.status.to_string()),
+            "loans",
+            "loans__cooperative_status",
+        ),
+    },
+);
+
+// Proposals are indexed by cooperative for paged listing
+pub struct ProposalIndexes<'a> {
+    pub cooperative: MultiIndex<'a, CorporativeName, Proposal, ProposalId>,
+}
+
+impl IndexList<Proposal> for ProposalIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Proposal>> + '_> {
+        let v: Vec<&dyn Index<Proposal>> = vec![&self.cooperative];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const PROPOSALS: IndexedMap<ProposalId, Proposal, ProposalIndexes> = IndexedMap::new(
+    "proposals",
+    ProposalIndexes {
+        cooperative: MultiIndex::new(
+            |_pk, p| p.cooperative.clone(),
+            "proposals",
+            "proposals__cooperative",
+        ),
+    },
+);
+
+// Votes are indexed by voter for per-voter history
+pub struct VoteIndexes<'a> {
+    pub voter: MultiIndex<'a, Addr, Vote, (ProposalId, Addr)>,
+}
+
+impl IndexList<Vote> for VoteIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Vote>> + '_> {
+        let v: Vec<&dyn Index<Vote>> = vec![&self.voter];
+        Box::new(v.into_iter())
+    }
+}
+
+pub const VOTES: IndexedMap<(ProposalId, Addr), Vote, VoteIndexes> = IndexedMap::new(
+    "votes",
+    VoteIndexes {
+        voter: MultiIndex::new(|_pk, v| v.voter.clone(), "votes", "votes__voter"),
+    },
+);
 
 pub const REWARDS_POOLS: Map<(CorporativeName, WhitelistedTokenId), CooperativeRewardsPool> =
     Map::new("rewards_pools");
+
+pub const MEMBER_REWARDS: Map<(CorporativeName, Addr, WhitelistedTokenId), MemberRewards> =
+    Map::new("member_rewards");
+
+// Withdrawals of each cooperative and token, filled in id order
+pub const WITHDRAWAL_QUEUE: Map<(CorporativeName, WhitelistedTokenId, u64), QueuedWithdrawal> =
+    Map::new("withdrawal_queue");